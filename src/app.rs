@@ -1,8 +1,11 @@
 use std::net::IpAddr;
 use strum::IntoEnumIterator;
 
+use crate::digital_id::DigitalId;
 use crate::error_template::{AppError, ErrorTemplate};
-use crate::{PendingCode, QuickUser};
+use crate::{
+    BankIdType, FailedHintCodes, OrderSummary, OrderType, PendingCode, QuickUser, RiskLevel,
+};
 use codee::string::JsonSerdeCodec;
 use itertools::Itertools;
 use js_sys::Date;
@@ -10,16 +13,16 @@ use leptos::prelude::*;
 use leptos::task::spawn_local;
 use leptos_meta::*;
 use leptos_router::components::*;
-use leptos_router::hooks::use_params_map;
+use leptos_router::hooks::{use_params_map, use_query_map};
 use leptos_router::path;
-use leptos_use::use_cookie;
+use leptos_use::{use_cookie, use_interval, UseIntervalReturn};
 use leptos_ws::ReadOnlySignal;
 use rand::distr::slice::Choose;
 use rand::distr::{Distribution, Uniform};
 use serde::Deserialize;
 use serde::Serialize;
 use time::macros::format_description;
-use time::{OffsetDateTime, UtcOffset};
+use time::UtcOffset;
 use uuid::Uuid;
 
 #[component]
@@ -58,6 +61,8 @@ pub fn App() -> impl IntoView {
                     path=path!("by-ip/:ip")
                     view=GetByIP />
                     <Route path=path!("by-alias/:alias") view=GetByAlias />
+                    <Route path=path!("app") view=MockApp />
+                    <Route path=path!("digital-id") view=DigitalIdPage />
                 </Routes>
             </main>
         </Router>
@@ -111,7 +116,11 @@ fn Navbar() -> impl IntoView {
                                             .collect_view()
                                     })
                             }}
-
+                            <li class="nav-item">
+                                <A href="/digital-id" {..} class="nav-link">
+                                    "Digital ID"
+                                </A>
+                            </li>
                         </ul>
                     </div>
                 </div>
@@ -230,12 +239,13 @@ fn GetByAlias() -> impl IntoView {
                                                 {o
                                                     .1
                                                     .iter()
-                                                    .sorted_by(|a, b| Ord::cmp(&a.0, &b.0).reverse())
+                                                    .sorted_by(|a, b| {
+                                                        Ord::cmp(&a.order_time, &b.order_time).reverse()
+                                                    })
                                                     .map(|n| {
                                                         view! {
                                                             <RenderOrder
-                                                                id=n.1
-                                                                time=n.0
+                                                                order=n.clone()
                                                                 quick_users=quick_users
                                                                 first_names=firstnames
                                                                 last_names=lastnames
@@ -318,12 +328,13 @@ fn GetByIP() -> impl IntoView {
                                                 {o
                                                     .1
                                                     .iter()
-                                                    .sorted_by(|a, b| Ord::cmp(&a.0, &b.0).reverse())
+                                                    .sorted_by(|a, b| {
+                                                        Ord::cmp(&a.order_time, &b.order_time).reverse()
+                                                    })
                                                     .map(|n| {
                                                         view! {
                                                             <RenderOrder
-                                                                id=n.1
-                                                                time=n.0
+                                                                order=n.clone()
                                                                 quick_users=quick_users
                                                                 first_names=firstnames
                                                                 last_names=lastnames
@@ -346,18 +357,374 @@ fn GetByIP() -> impl IntoView {
     }
 }
 
+/// Stands in for the BankID app, opened with `/app?autostarttoken=...&redirect=...`.
+#[component]
+fn MockApp() -> impl IntoView {
+    let query = use_query_map();
+    let token = move || {
+        query.with(|q| {
+            q.get("autostarttoken")
+                .and_then(|token| token.parse::<Uuid>().ok())
+        })
+    };
+    let redirect = move || query.with(|q| q.get("redirect")).filter(|r| r != "null");
+    let order = Resource::new(token, |token| async move {
+        match token {
+            Some(token) => get_order_by_auto_start_token(token).await,
+            None => Ok((Vec::new(), None)),
+        }
+    });
+    let complete_order = ServerAction::<CompleteOrder>::new();
+    let fail_order = ServerAction::<FailOrder>::new();
+    let is_done = move || {
+        complete_order.value().get().is_some_and(|r| r.is_ok())
+            || fail_order.value().get().is_some_and(|r| r.is_ok())
+    };
+    // The app prefers the returnUrl from the order over the redirect in the launch url. Only
+    // web urls are followed, anything else in the launch url could run scripts on the mock.
+    let return_to = Signal::derive(move || {
+        order
+            .get()
+            .and_then(|o| o.ok())
+            .and_then(|(_, o)| o)
+            .and_then(|o| o.request.return_url)
+            .or_else(redirect)
+            .filter(|url| crate::request::is_web_url(url))
+    });
+    Effect::new(move |_| {
+        if is_done() {
+            if let Some(url) = return_to.get() {
+                let _ = window().location().set_href(&url);
+            }
+        }
+    });
+    let (ssn, set_ssn) = signal("".to_string());
+    let (name, set_name) = signal("".to_string());
+
+    view! {
+        <Suspense>
+            <div class="container" style="max-width: 32rem">
+                {move || {
+                    order
+                        .get()
+                        .map(|result| match result {
+                            Ok((quick_users, Some(order))) => {
+                                let id = order.id;
+                                // The app offers a BankID the RP accepts, when the user has one.
+                                let bank_id_type = order
+                                    .request
+                                    .requirement
+                                    .clone()
+                                    .unwrap_or_default()
+                                    .allowed_bank_id_types()
+                                    .first()
+                                    .copied()
+                                    .unwrap_or_default();
+                                let action = match order.order_type {
+                                    OrderType::Auth => "Identify",
+                                    OrderType::Sign => "Sign",
+                                    OrderType::Payment => "Pay",
+                                };
+                                view! {
+                                    <div class="card">
+                                        <div class="card-header">"Mock BankID - " {action}</div>
+                                        <div class="card-body">
+                                            {order
+                                                .user_visible_data
+                                                .clone()
+                                                .map(|data| {
+                                                    view! { <pre class="text-wrap">{data}</pre> }
+                                                })}
+                                            <Show
+                                                when=move || !is_done()
+                                                fallback=move || {
+                                                    view! {
+                                                        <p>
+                                                            {move || {
+                                                                if return_to.get().is_some() {
+                                                                    "Returning to the service..."
+                                                                } else {
+                                                                    "Done, you can return to the service."
+                                                                }
+                                                            }}
+                                                        </p>
+                                                    }
+                                                }
+                                            >
+                                                <h6>"Who are you?"</h6>
+                                                {quick_users
+                                                    .clone()
+                                                    .into_iter()
+                                                    .map(|p| {
+                                                        view! {
+                                                            <ActionForm action=complete_order>
+                                                                <input type="text" name="id" value=id.to_string() hidden />
+                                                                <input name="ssn" value=p.ssn.to_string() hidden />
+                                                                <input name="name" value=p.name.to_string() hidden />
+                                                                <input
+                                                                    name="risk"
+                                                                    value=p.risk.unwrap_or_default().to_string()
+                                                                    hidden
+                                                                />
+                                                                <input
+                                                                    name="bank_id_type"
+                                                                    value=p.bank_id_type.unwrap_or(bank_id_type).to_string()
+                                                                    hidden
+                                                                />
+                                                                <input
+                                                                    type="submit"
+                                                                    class="btn btn-link"
+                                                                    value=p.label.clone()
+                                                                    disabled=move || complete_order.pending().get()
+                                                                />
+                                                            </ActionForm>
+                                                        }
+                                                    })
+                                                    .collect_view()}
+                                                <ActionForm action=complete_order>
+                                                    <input type="text" name="id" value=id.to_string() hidden />
+                                                    <input
+                                                        name="bank_id_type"
+                                                        value=bank_id_type.to_string()
+                                                        hidden
+                                                    />
+                                                    <div class="input-group mb-2">
+                                                        <button
+                                                            class="btn btn-outline-secondary"
+                                                            type="button"
+                                                            on:click=move |_| {
+                                                                set_ssn(generate_random_ssn());
+                                                            }
+                                                        >
+                                                            "Randomize"
+                                                        </button>
+                                                        <input
+                                                            type="text"
+                                                            name="ssn"
+                                                            class="form-control"
+                                                            placeholder="Ssn"
+                                                            on:input=move |ev| {
+                                                                set_ssn(event_target_value(&ev));
+                                                            }
+                                                            prop:value=ssn
+                                                        />
+                                                    </div>
+                                                    <input
+                                                        type="text"
+                                                        name="name"
+                                                        class="form-control mb-2"
+                                                        placeholder="Name"
+                                                        on:input=move |ev| {
+                                                            set_name(event_target_value(&ev));
+                                                        }
+                                                        prop:value=name
+                                                    />
+                                                    <input
+                                                        type="submit"
+                                                        class="btn btn-primary w-100"
+                                                        value=action
+                                                        disabled=move || complete_order.pending().get()
+                                                    />
+                                                </ActionForm>
+                                                <ActionForm action=fail_order>
+                                                    <input type="text" name="id" value=id.to_string() hidden />
+                                                    <input
+                                                        type="text"
+                                                        name="code"
+                                                        value=FailedHintCodes::UserCancel.to_string()
+                                                        hidden
+                                                    />
+                                                    <input
+                                                        type="submit"
+                                                        class="btn btn-outline-danger w-100 mt-2"
+                                                        value="Cancel"
+                                                        disabled=move || fail_order.pending().get()
+                                                    />
+                                                </ActionForm>
+                                                {move || {
+                                                    complete_order
+                                                        .value()
+                                                        .get()
+                                                        .and_then(|result| result.err())
+                                                        .map(|e| {
+                                                            view! { <div class="text-danger mt-2">{e.to_string()}</div> }
+                                                        })
+                                                }}
+                                            </Show>
+                                        </div>
+                                    </div>
+                                }
+                                    .into_any()
+                            }
+                            Ok((_, None)) => {
+                                view! {
+                                    <div class="alert alert-warning">
+                                        "No pending order for this autostarttoken"
+                                    </div>
+                                }
+                                    .into_any()
+                            }
+                            Err(o) => view! { <p>{o.to_string()}</p> }.into_any(),
+                        })
+                }}
+            </div>
+        </Suspense>
+    }
+}
+
+/// Issues mock digital ID cards, whose QR code an RP checks with `/rp/v6.0/verify`.
+#[component]
+fn DigitalIdPage() -> impl IntoView {
+    let options = Resource::new(|| (), |_| get_digital_id_options());
+    let issue = ServerAction::<IssueDigitalId>::new();
+    let (ssn, set_ssn) = signal("".to_string());
+    let (name, set_name) = signal("".to_string());
+
+    view! {
+        <Suspense>
+            <div class="container" style="max-width: 32rem">
+                <h5>"Digital ID card"</h5>
+                {move || {
+                    options
+                        .get()
+                        .map(|result| match result {
+                            Ok((quick_users, first_names, last_names)) => {
+                                view! {
+                                    {quick_users
+                                        .into_iter()
+                                        .map(|p| {
+                                            view! {
+                                                <ActionForm action=issue>
+                                                    <input name="ssn" value=p.ssn.to_string() hidden />
+                                                    <input name="name" value=p.name.to_string() hidden />
+                                                    <input
+                                                        type="submit"
+                                                        class="btn btn-link"
+                                                        value=p.label.clone()
+                                                        disabled=move || issue.pending().get()
+                                                    />
+                                                </ActionForm>
+                                            }
+                                        })
+                                        .collect_view()}
+                                    <ActionForm action=issue>
+                                        <div class="input-group mb-2">
+                                            <button
+                                                class="btn btn-outline-secondary"
+                                                type="button"
+                                                on:click=move |_| {
+                                                    set_ssn(generate_random_ssn());
+                                                    if !first_names.is_empty() && !last_names.is_empty() {
+                                                        let first_names = first_names
+                                                            .iter()
+                                                            .map(String::as_str)
+                                                            .collect::<Vec<_>>();
+                                                        let last_names = last_names
+                                                            .iter()
+                                                            .map(String::as_str)
+                                                            .collect::<Vec<_>>();
+                                                        set_name(generate_random_name(&first_names, &last_names));
+                                                    }
+                                                }
+                                            >
+                                                "Randomize"
+                                            </button>
+                                            <input
+                                                type="text"
+                                                name="ssn"
+                                                class="form-control"
+                                                placeholder="Ssn"
+                                                on:input=move |ev| {
+                                                    set_ssn(event_target_value(&ev));
+                                                }
+                                                prop:value=ssn
+                                            />
+                                        </div>
+                                        <input
+                                            type="text"
+                                            name="name"
+                                            class="form-control mb-2"
+                                            placeholder="Name"
+                                            on:input=move |ev| {
+                                                set_name(event_target_value(&ev));
+                                            }
+                                            prop:value=name
+                                        />
+                                        <input
+                                            type="submit"
+                                            class="btn btn-primary w-100"
+                                            value="Open ID card"
+                                            disabled=move || issue.pending().get()
+                                        />
+                                    </ActionForm>
+                                }
+                                    .into_any()
+                            }
+                            Err(o) => view! { <p>{o.to_string()}</p> }.into_any(),
+                        })
+                }}
+                {move || {
+                    issue
+                        .value()
+                        .get()
+                        .map(|result| match result {
+                            Ok((digital_id, svg)) => {
+                                view! {
+                                    <div class="card mt-3">
+                                        <div class="card-header">
+                                            {digital_id.user.name} " " {digital_id.user.personal_number}
+                                        </div>
+                                        <div class="card-body">
+                                            <div class="bg-white d-inline-block" inner_html=svg></div>
+                                            <pre class="text-wrap mt-2">{digital_id.qr_code}</pre>
+                                        </div>
+                                    </div>
+                                }
+                                    .into_any()
+                            }
+                            Err(o) => view! { <p>{o.to_string()}</p> }.into_any(),
+                        })
+                }}
+            </div>
+        </Suspense>
+    }
+}
+
 #[component]
 fn RenderOrder(
-    time: OffsetDateTime,
-    id: Uuid,
+    order: OrderSummary,
     quick_users: Signal<Vec<QuickUser>>,
     first_names: Signal<Vec<String>>,
     last_names: Signal<Vec<String>>,
 ) -> impl IntoView {
     let complete_order = ServerAction::<CompleteOrder>::new();
-    let (id, _) = signal(id);
+    let fail_order = ServerAction::<FailOrder>::new();
+    let scan_mrtd = ServerAction::<ScanMrtd>::new();
+    let is_done = move || {
+        complete_order.value().get().is_some_and(|r| r.is_ok())
+            || fail_order.value().get().is_some_and(|r| r.is_ok())
+            || scan_mrtd.value().get().is_some_and(|r| r.is_ok())
+    };
+    let time = order.order_time;
+    let (id, _) = signal(order.id);
     let (ssn, set_ssn) = signal("".to_string());
     let (name, set_name) = signal("".to_string());
+    // Left unset until the operator picks one, so quick users keep their own until then.
+    let (risk, set_risk) = signal(None::<RiskLevel>);
+    let requirement = order.request.requirement.clone().unwrap_or_default();
+    let allowed = requirement.allowed_bank_id_types();
+    // Shows a kind the RP accepts until the operator picks one, the others are kept to try
+    // out the failures. Quick users keep their own kind until then.
+    let default_bank_id_type = allowed.first().copied().unwrap_or_default();
+    let (bank_id_type, set_bank_id_type) = signal(None::<BankIdType>);
+    let (device_ip, set_device_ip) = signal("".to_string());
+    // Phone orders carry no endUserIp, their device gets a random ip unless one is given.
+    let device_ip_placeholder = match order.request.end_user_ip.as_str() {
+        "" => "Device ip, random".to_string(),
+        end_user_ip => format!("Device ip {end_user_ip}"),
+    };
+    // Overrides the issue date of quick users too, once the operator picks one.
+    let (issue_date, set_issue_date) = signal("".to_string());
     let format = format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
     let (offset, set_offset) = use_cookie::<UtcOffset, JsonSerdeCodec>("offset");
     if offset.get().is_none() {
@@ -372,7 +739,11 @@ fn RenderOrder(
     });
     view! {
         <tr>
-            <td>{move || id.get().to_string()}</td>
+            <td>
+                {move || id.get().to_string()}
+                <OrderDetails order=order.clone() />
+                {order.call_initiator.is_none().then(|| view! { <OrderQrCode id=order.id /> })}
+            </td>
             <td>
                 <form>
                     <select class="form-select" aria-label="Default select example">
@@ -399,6 +770,42 @@ fn RenderOrder(
             </td>
             <td>{move || time.to_offset(offset.get().unwrap()).format(&format).unwrap()}</td>
             <td>
+                {order
+                    .mrtd_user
+                    .clone()
+                    .map(|user| {
+                        view! {
+                            <div class="mb-2">
+                                <p class="mb-1">
+                                    {format!("Signed by {}, scanning their passport", user.name)}
+                                </p>
+                                <div class="d-inline-block">
+                                    <ActionForm action=scan_mrtd>
+                                        <input type="text" name="id" value=move || id.get().to_string() hidden />
+                                        <input type="text" name="passed" value="true" hidden />
+                                        <input
+                                            type="submit"
+                                            class="btn btn-success"
+                                            value="Passport ok"
+                                            disabled=move || scan_mrtd.pending().get() || is_done()
+                                        />
+                                    </ActionForm>
+                                </div>
+                                <div class="d-inline-block ms-2">
+                                    <ActionForm action=scan_mrtd>
+                                        <input type="text" name="id" value=move || id.get().to_string() hidden />
+                                        <input type="text" name="passed" value="false" hidden />
+                                        <input
+                                            type="submit"
+                                            class="btn btn-outline-warning"
+                                            value="Passport failed"
+                                            disabled=move || scan_mrtd.pending().get() || is_done()
+                                        />
+                                    </ActionForm>
+                                </div>
+                            </div>
+                        }
+                    })}
                 <ActionForm action=complete_order>
                     // {..} class="row row-cols-lg-auto g-3 align-items-center"
                     <input type="text" name="id" value=move || id.get().to_string() hidden />
@@ -487,15 +894,98 @@ fn RenderOrder(
                         </div>
                     </div>
 
+                    <div class="col-12">
+                        <select
+                            class="form-select"
+                            name="risk"
+                            aria-label="Risk"
+                            on:change=move |ev| {
+                                if let Some(level) = RiskLevel::iter()
+                                    .find(|level| level.to_string() == event_target_value(&ev))
+                                {
+                                    set_risk(Some(level));
+                                }
+                            }
+                        >
+                            {RiskLevel::iter()
+                                .map(|level| {
+                                    view! {
+                                        <option
+                                            value=level.to_string()
+                                            selected=move || risk.get().unwrap_or_default() == level
+                                        >
+                                            {format!("{} risk", level)}
+                                        </option>
+                                    }
+                                })
+                                .collect_view()}
+                        </select>
+                    </div>
+                    <div class="col-12">
+                        <select
+                            class="form-select"
+                            name="bank_id_type"
+                            aria-label="Kind of BankID"
+                            on:change=move |ev| {
+                                if let Some(kind) = BankIdType::iter()
+                                    .find(|kind| kind.to_string() == event_target_value(&ev))
+                                {
+                                    set_bank_id_type(Some(kind));
+                                }
+                            }
+                        >
+                            {BankIdType::iter()
+                                .map(|kind| {
+                                    view! {
+                                        <option
+                                            value=kind.to_string()
+                                            selected=move || {
+                                                bank_id_type.get().unwrap_or(default_bank_id_type)
+                                                    == kind
+                                            }
+                                        >
+                                            {if allowed.contains(&kind) {
+                                                format!("{} BankID", kind)
+                                            } else {
+                                                format!("{} BankID (not allowed)", kind)
+                                            }}
+                                        </option>
+                                    }
+                                })
+                                .collect_view()}
+                        </select>
+                    </div>
+                    <div class="col-12">
+                        <input
+                            type="text"
+                            name="device_ip"
+                            class="form-control"
+                            aria-label="Device ip"
+                            placeholder=device_ip_placeholder
+                            on:input=move |ev| {
+                                set_device_ip(event_target_value(&ev));
+                            }
+                            prop:value=device_ip
+                        />
+                    </div>
+                    <div class="col-12">
+                        <input
+                            type="date"
+                            name="bank_id_issue_date"
+                            class="form-control"
+                            aria-label="BankID issue date"
+                            on:input=move |ev| {
+                                set_issue_date(event_target_value(&ev));
+                            }
+                            prop:value=issue_date
+                        />
+                    </div>
                     <div class="col-12">
                         <input
                             type="submit"
                             class="btn btn-primary"
                             value="Submit"
-                            disabled=move || {
-                                complete_order.pending().get()
-                                    || complete_order.value().get().is_some()
-                            }
+                            disabled=move || complete_order.pending().get() || is_done()
                         />
 
                     </div>
@@ -518,30 +1008,263 @@ fn RenderOrder(
                                 />
                                 <input name="ssn" value=p.ssn.to_string() hidden />
                                 <input name="name" value=p.name.to_string() hidden />
+                                <input
+                                    name="risk"
+                                    value=move || risk.get().or(p.risk).unwrap_or_default().to_string()
+                                    hidden
+                                />
+                                <input
+                                    name="bank_id_type"
+                                    value=move || {
+                                        bank_id_type
+                                            .get()
+                                            .or(p.bank_id_type)
+                                            .unwrap_or(default_bank_id_type)
+                                            .to_string()
+                                    }
+                                    hidden
+                                />
+                                <input name="device_ip" value=device_ip hidden />
+                                <input
+                                    name="bank_id_issue_date"
+                                    value=move || {
+                                        Some(issue_date.get())
+                                            .filter(|date| !date.is_empty())
+                                            .or_else(|| {
+                                                p.bank_id_issue_date
+                                                    .and_then(|date| date.format(crate::ISSUE_DATE_FORMAT).ok())
+                                            })
+                                            .unwrap_or_default()
+                                    }
+                                    hidden
+                                />
                                 <input
                                     type="submit"
                                     class="btn btn-link"
                                     value=p.label.clone()
-                                    disabled=move || {
-                                        complete_order.pending().get()
-                                            || complete_order.value().get().is_some()
-                                    }
+                                    disabled=move || complete_order.pending().get() || is_done()
                                 />
 
                             </ActionForm>
                         }
                     })
                     .collect_view()}
+                {move || {
+                    complete_order
+                        .value()
+                        .get()
+                        .and_then(|result| result.err())
+                        .map(|e| view! { <div class="text-danger">{e.to_string()}</div> })
+                }}
+
+                <ActionForm action=fail_order>
+                    <input type="text" name="id" value=move || id.get().to_string() hidden />
+                    <input type="text" name="code" value=FailedHintCodes::UserCancel.to_string() hidden />
+                    <input
+                        type="submit"
+                        class="btn btn-outline-danger"
+                        value="Cancel as user"
+                        disabled=move || fail_order.pending().get() || is_done()
+                    />
+                </ActionForm>
+
+                {order
+                    .call_initiator
+                    .is_some()
+                    .then(|| {
+                        view! {
+                            <ActionForm action=fail_order>
+                                <input
+                                    type="text"
+                                    name="id"
+                                    value=move || id.get().to_string()
+                                    hidden
+                                />
+                                <input
+                                    type="text"
+                                    name="code"
+                                    value=FailedHintCodes::UserDeclinedCall.to_string()
+                                    hidden
+                                />
+                                <input
+                                    type="submit"
+                                    class="btn btn-outline-danger"
+                                    value="Decline call"
+                                    disabled=move || fail_order.pending().get() || is_done()
+                                />
+                            </ActionForm>
+                        }
+                    })}
+
+                <ActionForm action=fail_order>
+                    <input type="text" name="id" value=move || id.get().to_string() hidden />
+                    <div class="input-group">
+                        <select
+                            class="form-select"
+                            name="code"
+                            aria-label="Failure hint code"
+                        >
+                            {FailedHintCodes::iter()
+                                .map(|code| {
+                                    view! { <option value=code.to_string()>{code.to_string()}</option> }
+                                })
+                                .collect_view()}
+                        </select>
+                        <input
+                            type="submit"
+                            class="btn btn-outline-danger"
+                            value="Fail with…"
+                            disabled=move || fail_order.pending().get() || is_done()
+                        />
+                    </div>
+                </ActionForm>
 
             </td>
         </tr>
     }
 }
 
+/// The RP supplied parts of an order, shown under its id.
+#[component]
+fn OrderDetails(order: OrderSummary) -> impl IntoView {
+    let request = order.request;
+    let requirement = request.requirement.clone().unwrap_or_default();
+    let device = match (&request.app, &request.web) {
+        (Some(app), _) => Some(format!(
+            "App: {} on {} ({})",
+            app.app_identifier, app.device_os, app.device_model_name
+        )),
+        (_, Some(web)) => Some(format!(
+            "Web: {} ({})",
+            web.referring_domain, web.user_agent
+        )),
+        _ => None,
+    };
+    let field = |label: &'static str, value: Option<String>| {
+        value.map(|value| {
+            view! {
+                <dt>{label}</dt>
+                <dd>{value}</dd>
+            }
+        })
+    };
+
+    view! {
+        {(order.order_type == OrderType::Sign)
+            .then(|| view! { <span class="badge text-bg-warning ms-2">"Sign"</span> })}
+        {(order.order_type == OrderType::Payment)
+            .then(|| view! { <span class="badge text-bg-success ms-2">"Payment"</span> })}
+        {order
+            .call_initiator
+            .map(|initiator| {
+                view! {
+                    <span class="badge text-bg-info ms-2">
+                        "Phone, called by " {initiator.to_string()}
+                    </span>
+                }
+            })}
+        {request
+            .user_visible_transaction
+            .clone()
+            .map(|transaction| {
+                let payment = match &transaction.money {
+                    Some(money) => {
+                        format!(
+                            "Pay {} {} to {}",
+                            money.amount,
+                            money.currency,
+                            transaction.recipient.name,
+                        )
+                    }
+                    None => format!("Pay {}", transaction.recipient.name),
+                };
+                view! {
+                    <div class="card border-success mt-2 small">
+                        <div class="card-body p-2">
+                            <div class="fw-bold">{payment}</div>
+                            <div class="text-body-secondary">
+                                {format!("Transaction type: {}", transaction.transaction_type)}
+                            </div>
+                            {transaction
+                                .risk_warning
+                                .map(|warning| view! { <div class="text-danger">{warning}</div> })}
+                        </div>
+                    </div>
+                }
+            })}
+        <dl class="mt-2 mb-0 small">
+            {order
+                .user_visible_data
+                .map(|data| {
+                    view! {
+                        <dt>
+                            "Visible data"
+                            {request.user_visible_data_format.map(|f| format!(" ({})", f))}
+                        </dt>
+                        <dd>
+                            <pre class="mb-0 text-wrap">{data}</pre>
+                        </dd>
+                    }
+                })}
+            {order
+                .user_non_visible_data
+                .map(|data| {
+                    view! {
+                        <dt>"Non-visible data"</dt>
+                        <dd>
+                            <pre class="mb-0 text-wrap text-body-secondary">{data}</pre>
+                        </dd>
+                    }
+                })}
+        </dl>
+        <details class="small">
+            <summary>"Request"</summary>
+            <dl class="mb-0">
+                {field("Relying party", order.rp.clone())}
+                {field("End user ip", Some(request.end_user_ip.clone()))}
+                {field("Personal number", requirement.personal_number.clone())}
+                {field("Pin code", requirement.pin_code.map(|v| v.to_string()))}
+                {field("Mrtd", requirement.mrtd.map(|v| v.to_string()))}
+                {field("Card reader", requirement.card_reader.map(|v| v.to_string()))}
+                {field(
+                    "Certificate policies",
+                    requirement.certificate_policies.clone().map(|p| p.join(", ")),
+                )}
+                {field("Return url", request.return_url.clone())}
+                {field("Return risk", request.return_risk.map(|v| v.to_string()))}
+                {field(
+                    "Risk flags",
+                    request.risk_flags.clone().map(|flags| flags.iter().join(", ")),
+                )}
+                {field("Device", device)}
+            </dl>
+        </details>
+    }
+}
+
+/// The animated QR code of a pending order, refreshed every second like in the real app.
+#[component]
+fn OrderQrCode(id: Uuid) -> impl IntoView {
+    let UseIntervalReturn { counter, .. } = use_interval(1000);
+    let qr_code = Resource::new(move || counter.get(), move |_| get_qr_code(id));
+
+    view! {
+        <details class="small">
+            <summary>"QR code"</summary>
+            <Transition>
+                {move || {
+                    qr_code
+                        .get()
+                        .and_then(|svg| svg.ok().flatten())
+                        .map(|svg| view! { <div class="bg-white d-inline-block" inner_html=svg></div> })
+                }}
+            </Transition>
+        </details>
+    }
+}
+
 #[server(GetOrders, "/api")]
-pub async fn get_orders(
-    ip: IpAddr,
-) -> Result<(Vec<QuickUser>, Vec<(OffsetDateTime, Uuid)>), ServerFnError> {
+pub async fn get_orders(ip: IpAddr) -> Result<(Vec<QuickUser>, Vec<OrderSummary>), ServerFnError> {
     let orders =
         use_context::<crate::Orders>().ok_or_else(|| ServerFnError::new("Orders missing."))?;
     let config =
@@ -555,7 +1278,7 @@ pub async fn get_orders(
 #[server(GetOrdersByAlias, "/api")]
 pub async fn get_orders_by_alias(
     alias: String,
-) -> Result<(Vec<QuickUser>, Vec<(OffsetDateTime, Uuid)>), ServerFnError> {
+) -> Result<(Vec<QuickUser>, Vec<OrderSummary>), ServerFnError> {
     let orders =
         use_context::<crate::Orders>().ok_or_else(|| ServerFnError::new("Orders missing."))?;
     let config =
@@ -564,7 +1287,7 @@ pub async fn get_orders_by_alias(
     let quick_users = &config.quick_users.clone().unwrap_or_default();
     let ord = orders.lock().unwrap();
 
-    let orders: Vec<_> = config
+    let mut orders: Vec<_> = config
         .aliases
         .as_ref()
         .map(|q| {
@@ -574,11 +1297,27 @@ pub async fn get_orders_by_alias(
                 .flat_map(|p| ord.get_all(&p.ip))
                 .collect()
         })
-        .unwrap();
+        .unwrap_or_default();
+    // RPs identified by client certificate are listed by their name like an alias.
+    orders.extend(ord.get_all_for_rp(&alias));
 
     Ok((quick_users.to_vec(), orders))
 }
 
+#[server]
+pub async fn get_order_by_auto_start_token(
+    token: Uuid,
+) -> Result<(Vec<QuickUser>, Option<OrderSummary>), ServerFnError> {
+    let orders =
+        use_context::<crate::Orders>().ok_or_else(|| ServerFnError::new("Orders missing."))?;
+    let config =
+        use_context::<crate::ConfigState>().ok_or_else(|| ServerFnError::new("Config missing."))?;
+
+    let quick_users = config.quick_users.clone().unwrap_or_default();
+    let ord = orders.lock().unwrap();
+    Ok((quick_users, ord.get_by_auto_start_token(&token)))
+}
+
 #[server]
 pub async fn get_first_and_lastname_options() -> Result<(Vec<String>, Vec<String>), ServerFnError> {
     let config =
@@ -589,14 +1328,49 @@ pub async fn get_first_and_lastname_options() -> Result<(Vec<String>, Vec<String
 
     Ok((first_names, last_names))
 }
+#[server]
+pub async fn get_digital_id_options(
+) -> Result<(Vec<QuickUser>, Vec<String>, Vec<String>), ServerFnError> {
+    let config =
+        use_context::<crate::ConfigState>().ok_or_else(|| ServerFnError::new("Config missing."))?;
+
+    Ok((
+        config.quick_users.clone().unwrap_or_default(),
+        config.first_names.clone().unwrap_or_default(),
+        config.last_names.clone().unwrap_or_default(),
+    ))
+}
+
+#[server]
+pub async fn issue_digital_id(
+    ssn: String,
+    name: String,
+) -> Result<(DigitalId, String), ServerFnError> {
+    let orders =
+        use_context::<crate::Orders>().ok_or_else(|| ServerFnError::new("Orders missing."))?;
+    let mut ord = orders.lock().unwrap();
+    let digital_id = ord.issue_digital_id(crate::UserCompletionData::new(ssn, name));
+    let svg = crate::qr::render_svg(&digital_id.qr_code);
+    Ok((digital_id, svg))
+}
+
 #[server(GetAlias, "/api")]
 pub async fn get_aliases() -> Result<Vec<String>, ServerFnError> {
     let config = use_context::<crate::ConfigState>()
         .ok_or_else(|| ServerFnError::new("Config missing p."))?;
 
+    let orders =
+        use_context::<crate::Orders>().ok_or_else(|| ServerFnError::new("Orders missing."))?;
+
     let aliases = config.aliases.as_ref().cloned().unwrap_or_default();
+    let rps = orders.lock().unwrap().get_rps();
 
-    let alias_names = aliases.iter().map(|f| f.name.clone()).collect();
+    let alias_names = aliases
+        .iter()
+        .map(|f| f.name.clone())
+        .chain(rps)
+        .unique()
+        .collect();
     Ok(alias_names)
 }
 #[server]
@@ -620,6 +1394,7 @@ pub async fn get_ips() -> Result<Vec<IpEntry>, ServerFnError> {
                 IpEntry::JustIp(f.clone())
             }
         })
+        .chain(ord.get_rps().into_iter().map(IpEntry::Alias))
         .collect();
     drop(ord);
     Ok(ips)
@@ -632,36 +1407,101 @@ pub enum IpEntry {
 }
 
 #[server]
-pub async fn complete_order(id: Uuid, ssn: String, name: String) -> Result<(), ServerFnError> {
+pub async fn complete_order(
+    id: Uuid,
+    ssn: String,
+    name: String,
+    risk: Option<RiskLevel>,
+    bank_id_type: Option<BankIdType>,
+    device_ip: Option<String>,
+    bank_id_issue_date: Option<String>,
+) -> Result<(), ServerFnError> {
     let orders =
         use_context::<crate::Orders>().ok_or_else(|| ServerFnError::new("Orders missing."))?;
 
+    // Left empty in the form for the endUserIp and a random issue date.
+    let device_ip = device_ip
+        .filter(|ip| !ip.is_empty())
+        .map(|ip| ip.parse())
+        .transpose()
+        .map_err(|_| ServerFnError::new("Invalid device ip."))?;
+    let bank_id_issue_date = bank_id_issue_date
+        .filter(|date| !date.is_empty())
+        .map(|date| time::Date::parse(&date, crate::ISSUE_DATE_FORMAT))
+        .transpose()
+        .map_err(|_| ServerFnError::new("Invalid issue date."))?;
+
     let count = ReadOnlySignal::new("counter", 9).unwrap();
 
     let mut ord = orders.lock().unwrap();
-    let new_name = name.clone();
-    let mut split = new_name.split_whitespace();
-    let given = split.nth(0).unwrap_or("");
-    let surname = split.last().unwrap_or("");
-    ord.upgrade(
+    let completed = ord.upgrade(
         id,
-        crate::UserCompletionData {
-            personal_number: ssn,
-            name,
-            given_name: given.to_string(),
-            sur_name: surname.to_string(),
+        crate::UserCompletionData::new(ssn, name),
+        crate::CompletionOptions {
+            risk: risk.unwrap_or_default(),
+            bank_id_type: bank_id_type.unwrap_or_default(),
+            device_ip,
+            bank_id_issue_date,
         },
     );
+    if !completed {
+        return Err(ServerFnError::new("Order is no longer pending."));
+    }
     count.update(|x| *x += 1);
 
     Ok(())
 }
+#[server]
+pub async fn fail_order(id: Uuid, code: FailedHintCodes) -> Result<(), ServerFnError> {
+    let orders =
+        use_context::<crate::Orders>().ok_or_else(|| ServerFnError::new("Orders missing."))?;
+
+    let count = ReadOnlySignal::new("counter", 9).unwrap();
+
+    let mut ord = orders.lock().unwrap();
+    if !ord.fail(id, code) {
+        return Err(ServerFnError::new("Order is no longer pending."));
+    }
+    count.update(|x| *x += 1);
+
+    Ok(())
+}
+#[server]
+pub async fn get_qr_code(id: Uuid) -> Result<Option<String>, ServerFnError> {
+    let orders =
+        use_context::<crate::Orders>().ok_or_else(|| ServerFnError::new("Orders missing."))?;
+    let ord = orders.lock().unwrap();
+
+    Ok(ord
+        .current_qr_data(&id)
+        .map(|data| crate::qr::render_svg(&data)))
+}
+#[server]
+pub async fn scan_mrtd(id: Uuid, passed: bool) -> Result<(), ServerFnError> {
+    let orders =
+        use_context::<crate::Orders>().ok_or_else(|| ServerFnError::new("Orders missing."))?;
+
+    let count = ReadOnlySignal::new("counter", 9).unwrap();
+
+    let mut ord = orders.lock().unwrap();
+    if !ord.scan_mrtd(&id, passed) {
+        return Err(ServerFnError::new(
+            "Order is not waiting for a passport scan",
+        ));
+    }
+    count.update(|x| *x += 1);
+
+    Ok(())
+}
+
 #[server]
 pub async fn update_pending_status(id: Uuid, status: PendingCode) -> Result<(), ServerFnError> {
     let orders =
         use_context::<crate::Orders>().ok_or_else(|| ServerFnError::new("Orders missing."))?;
     let mut ord = orders.lock().unwrap();
-    ord.set_pending_status(id, status);
+    if !ord.set_pending_status(id, status) {
+        return Err(ServerFnError::new("Order is no longer pending."));
+    }
 
     Ok(())
 }
@@ -0,0 +1,373 @@
+use std::net::IpAddr;
+
+use serde::Deserialize;
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    Config, FailedHintCodes, Order, OrderData, OrderEnum, PendingCode, PendingData,
+    UserCompletionData,
+};
+
+/// Steps taken on an RP's orders without anyone using the UI, for automated tests.
+///
+/// An automation applies to orders from the `alias` (or the RP named by its client certificate),
+/// or from the `ip`, which may be a CIDR range. When `personal-number` is set, the order must
+/// also require it, and without an alias or ip that is enough. At least one of them has to be
+/// set, and the first automation matching an order is used.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case", try_from = "AutomationConfig")]
+pub struct Automation {
+    pub alias: Option<String>,
+    pub ip: Option<IpRange>,
+    /// Scripts the outcome for a persona, matched against `requirement.personalNumber`.
+    pub personal_number: Option<String>,
+    pub steps: Vec<AutomationStep>,
+}
+
+/// An [`Automation`] as written in the config, before checking it selects some orders.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct AutomationConfig {
+    alias: Option<String>,
+    ip: Option<IpRange>,
+    personal_number: Option<String>,
+    steps: Vec<AutomationStep>,
+}
+
+impl TryFrom<AutomationConfig> for Automation {
+    type Error = &'static str;
+
+    fn try_from(value: AutomationConfig) -> Result<Self, Self::Error> {
+        if value.alias.is_none() && value.ip.is_none() && value.personal_number.is_none() {
+            return Err("An automation needs an alias, ip or personal-number");
+        }
+        Ok(Automation {
+            alias: value.alias,
+            ip: value.ip,
+            personal_number: value.personal_number,
+            steps: value.steps,
+        })
+    }
+}
+
+/// What to do with a pending order once it is `after` seconds old.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct AutomationStep {
+    pub after: u32,
+    #[serde(flatten)]
+    pub action: AutomationAction,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub enum AutomationAction {
+    /// Moves the order to another pending status, like `userSign`.
+    Status(PendingCode),
+    /// Completes the order as the quick user with the label.
+    Complete(String),
+    Fail(FailedHintCodes),
+    /// Finishes the passport scan of an order waiting in userMrtd, passed or not.
+    Mrtd(bool),
+}
+
+/// A single ip, or every ip in a CIDR range like `10.0.0.0/8`.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(try_from = "String")]
+pub struct IpRange {
+    network: IpAddr,
+    prefix: u32,
+}
+
+impl IpRange {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                u32::from(network) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                u128::from(network) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl TryFrom<String> for IpRange {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (network, prefix) = value.split_once('/').unwrap_or((&value, ""));
+        let network: IpAddr = network
+            .parse()
+            .map_err(|_| format!("Invalid ip in {value}"))?;
+        let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            "" => max_prefix,
+            prefix => prefix
+                .parse()
+                .ok()
+                .filter(|prefix| *prefix <= max_prefix)
+                .ok_or_else(|| format!("Invalid prefix length in {value}"))?,
+        };
+        Ok(IpRange { network, prefix })
+    }
+}
+
+impl Automation {
+    fn applies_to(&self, config: &Config, order: &Order) -> bool {
+        let rp = order.rp.as_deref();
+        let alias_matches = self.alias.as_deref().is_some_and(|alias| {
+            rp == Some(alias)
+                || config
+                    .aliases
+                    .iter()
+                    .flatten()
+                    .any(|a| a.name == alias && a.ip == order.ip)
+        });
+        let ip_matches = self
+            .ip
+            .is_some_and(|range| rp.is_none() && range.contains(&order.ip));
+        let source_matches =
+            self.alias.is_none() && self.ip.is_none() || alias_matches || ip_matches;
+        let personal_number_matches = self
+            .personal_number
+            .as_deref()
+            .is_none_or(|personal_number| order.personal_number() == Some(personal_number));
+        source_matches && personal_number_matches
+    }
+}
+
+impl Config {
+    /// Checks that automations for a personal number complete as the quick user with it, so
+    /// the RP gets back the person it asked for.
+    pub fn check_automations(&self) -> Result<(), String> {
+        for automation in self.automations.iter().flatten() {
+            let Some(personal_number) = &automation.personal_number else {
+                continue;
+            };
+            for step in &automation.steps {
+                let AutomationAction::Complete(label) = &step.action else {
+                    continue;
+                };
+                let other_user = self
+                    .quick_users
+                    .iter()
+                    .flatten()
+                    .find(|user| &user.label == label)
+                    .filter(|user| &user.ssn != personal_number);
+                if let Some(user) = other_user {
+                    return Err(format!(
+                        "Automation for {personal_number} completes as {label}, who has {}",
+                        user.ssn
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl OrderData {
+    /// Takes the latest due step of the automation for every pending order, returns how many
+    /// orders changed.
+    pub fn automate(&mut self, config: &Config) -> usize {
+        let Some(automations) = &config.automations else {
+            return 0;
+        };
+        let now = OffsetDateTime::now_utc();
+        let mut changed = 0;
+        for order in self.data.values_mut() {
+            let OrderEnum::Pending(pending) = &order.data else {
+                continue;
+            };
+            let Some(automation) = automations.iter().find(|a| a.applies_to(config, order)) else {
+                continue;
+            };
+            let Some(step) = automation
+                .steps
+                .iter()
+                .filter(|step| order.order_time + Duration::seconds(step.after.into()) <= now)
+                .max_by_key(|step| step.after)
+            else {
+                continue;
+            };
+            match &step.action {
+                AutomationAction::Status(status) if &pending.status != status => {
+                    order.data = OrderEnum::Pending(PendingData {
+                        status: status.clone(),
+                    });
+                }
+                AutomationAction::Status(_) => continue,
+                // Already signed, waiting for the passport scan.
+                AutomationAction::Complete(_) if order.mrtd_user.is_some() => continue,
+                AutomationAction::Complete(label) => {
+                    let Some(user) = config
+                        .quick_users
+                        .iter()
+                        .flatten()
+                        .find(|user| &user.label == label)
+                    else {
+                        leptos::logging::warn!(
+                            "Automation completes as unknown quick user {label}"
+                        );
+                        continue;
+                    };
+                    order.complete(
+                        UserCompletionData::new(user.ssn.clone(), user.name.clone()),
+                        user.completion_options(),
+                    );
+                }
+                AutomationAction::Fail(code) => {
+                    order.finish(OrderEnum::Failed(code.clone()));
+                }
+                AutomationAction::Mrtd(passed) => {
+                    if !order.scan_mrtd(*passed) {
+                        continue;
+                    }
+                }
+            }
+            changed += 1;
+        }
+        if changed > 0 {
+            self.save();
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::request::{AuthRequest, Requirement};
+    use crate::OrderType;
+
+    const CONFIG: &str = r#"
+        [[aliases]]
+        ip = "127.0.0.2"
+        name = "Prod"
+    "#;
+
+    fn automation(toml: &str) -> Automation {
+        let mut config: Config = toml::from_str(&format!("{toml}\nsteps = []")).unwrap();
+        config.automations.take().unwrap().remove(0)
+    }
+
+    fn order(ip: &str, rp: Option<&str>, personal_number: Option<&str>) -> Order {
+        let mut orders = OrderData::new();
+        let id = Uuid::new_v4();
+        let request = AuthRequest {
+            end_user_ip: ip.to_string(),
+            requirement: Some(Requirement {
+                personal_number: personal_number.map(str::to_string),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        orders.insert_empty(
+            id,
+            ip.parse().unwrap(),
+            OrderType::Auth,
+            request,
+            None,
+            rp.map(str::to_string),
+        );
+        orders.data.remove(&id).unwrap()
+    }
+
+    fn applies(toml: &str, order: &Order) -> bool {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        automation(&format!("[[automations]]\n{toml}")).applies_to(&config, order)
+    }
+
+    #[test]
+    fn alias_or_ip_selects_orders() {
+        let prod = order("127.0.0.2", None, None);
+        let other = order("10.1.2.3", None, None);
+        let rp = order("10.1.2.3", Some("Prod"), None);
+        let both = "alias = \"Prod\"\nip = \"10.0.0.0/8\"";
+        assert!(applies(both, &prod));
+        assert!(applies(both, &other));
+        assert!(applies(both, &rp));
+        assert!(applies("alias = \"Prod\"", &prod));
+        assert!(!applies("alias = \"Prod\"", &other));
+        assert!(!applies("ip = \"10.0.0.0/8\"", &rp));
+    }
+
+    #[test]
+    fn client_certificate_rps_match_aliases_by_ip() {
+        let rp = order("127.0.0.2", Some("Test RP"), None);
+        assert!(applies("alias = \"Prod\"", &rp));
+        assert!(applies("alias = \"Test RP\"", &rp));
+    }
+
+    #[test]
+    fn personal_number_narrows_the_selected_orders() {
+        let persona = order("10.1.2.3", None, Some("201604064188"));
+        let someone_else = order("10.1.2.3", None, Some("198001011234"));
+        let prod_persona = order("127.0.0.2", None, Some("201604064188"));
+        let only_persona = "personal-number = \"201604064188\"";
+        assert!(applies(only_persona, &persona));
+        assert!(applies(only_persona, &prod_persona));
+        assert!(!applies(only_persona, &someone_else));
+        let prod_persona_only = "alias = \"Prod\"\npersonal-number = \"201604064188\"";
+        assert!(applies(prod_persona_only, &prod_persona));
+        assert!(!applies(prod_persona_only, &persona));
+    }
+
+    #[test]
+    fn automations_without_selector_are_rejected() {
+        let error = toml::from_str::<Config>("[[automations]]\nsteps = []").unwrap_err();
+        assert!(error
+            .message()
+            .contains("needs an alias, ip or personal-number"));
+    }
+
+    #[test]
+    fn ip_ranges_contain_their_network() {
+        let range = IpRange::try_from("10.0.0.0/8".to_string()).unwrap();
+        assert!(range.contains(&"10.255.0.1".parse().unwrap()));
+        assert!(!range.contains(&"11.0.0.1".parse().unwrap()));
+        assert!(!range.contains(&"::1".parse().unwrap()));
+        let single = IpRange::try_from("192.168.1.1".to_string()).unwrap();
+        assert!(single.contains(&"192.168.1.1".parse().unwrap()));
+        assert!(!single.contains(&"192.168.1.2".parse().unwrap()));
+        let all = IpRange::try_from("0.0.0.0/0".to_string()).unwrap();
+        assert!(all.contains(&"8.8.8.8".parse().unwrap()));
+        let v6 = IpRange::try_from("fd00::/8".to_string()).unwrap();
+        assert!(v6.contains(&"fd12::1".parse().unwrap()));
+        assert!(IpRange::try_from("10.0.0.0/33".to_string()).is_err());
+        assert!(IpRange::try_from("not an ip".to_string()).is_err());
+    }
+
+    #[test]
+    fn personal_number_automations_complete_as_that_person() {
+        let config = |complete: &str| {
+            toml::from_str::<Config>(&format!(
+                r#"
+                [[quick-users]]
+                label = "test"
+                ssn = "201604064188"
+                name = "Test Testsson"
+
+                [[automations]]
+                personal-number = "201604064188"
+                steps = [{{ after = 1, complete = "{complete}" }}]
+                "#
+            ))
+            .unwrap()
+        };
+        assert_eq!(config("test").check_automations(), Ok(()));
+
+        let mut other = config("test");
+        other.automations.as_mut().unwrap()[0].personal_number = Some("198001011234".into());
+        assert_eq!(
+            other.check_automations(),
+            Err("Automation for 198001011234 completes as test, who has 201604064188".to_string())
+        );
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType, IsCa, KeyPair,
+    KeyUsagePurpose,
+};
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+use ring::rand::SystemRandom;
+use ring::signature::{
+    EcdsaKeyPair, EcdsaSigningAlgorithm, ECDSA_P256_SHA256_ASN1_SIGNING,
+    ECDSA_P256_SHA256_FIXED_SIGNING,
+};
+use time::{Duration, OffsetDateTime};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::CertificateDer;
+use x509_parser::prelude::{FromDer, X509Certificate};
+use yasna::models::{GeneralizedTime, ObjectIdentifier};
+use yasna::{DERWriter, Tag};
+
+use crate::{CaConfig, UserCompletionData};
+
+const SERIAL_NUMBER: &[u64] = &[2, 5, 4, 5];
+const SURNAME: &[u64] = &[2, 5, 4, 4];
+const GIVEN_NAME: &[u64] = &[2, 5, 4, 42];
+const SHA1: &[u64] = &[1, 3, 14, 3, 2, 26];
+const ECDSA_WITH_SHA256: &[u64] = &[1, 2, 840, 10045, 4, 3, 2];
+const OCSP_BASIC: &[u64] = &[1, 3, 6, 1, 5, 5, 7, 48, 1, 1];
+const OCSP_NONCE: &[u64] = &[1, 3, 6, 1, 5, 5, 7, 48, 1, 2];
+
+/// A stand-in for the BankID CA chain, a root CA with an issuing CA that signs the users.
+///
+/// Generated on startup unless kept in the files of a [`CaConfig`], in which case RPs can
+/// keep trusting the same root across restarts.
+pub struct MockCa {
+    root: CertificateDer<'static>,
+    issuing: CertificateDer<'static>,
+    /// The issuing CA as rcgen signs with it, rebuilt from `issuing` when loaded.
+    issuer: Certificate,
+    issuing_key: KeyPair,
+    pem_chain: String,
+    users: Mutex<HashMap<String, Arc<UserCertificate>>>,
+}
+
+/// The certificate a user signs with, reused for every order by the same personal number.
+pub struct UserCertificate {
+    pub certificate: Certificate,
+    key: KeyPair,
+}
+
+impl MockCa {
+    pub fn generate() -> Self {
+        let now = OffsetDateTime::now_utc();
+
+        let root_key = KeyPair::generate().expect("Generating the root CA key");
+        let mut root = CertificateParams::default();
+        root.distinguished_name = name("Mock BankID Root CA v1 Test");
+        root.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        root.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+        root.not_before = now - Duration::days(1);
+        root.not_after = now + Duration::days(20 * 365);
+        let root = root.self_signed(&root_key).expect("Signing the root CA");
+
+        let issuing_key = KeyPair::generate().expect("Generating the issuing CA key");
+        let mut issuing = CertificateParams::default();
+        issuing.distinguished_name = name("Mock BankID Customer CA v1 Test");
+        issuing.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+        issuing.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+        issuing.not_before = now - Duration::days(1);
+        issuing.not_after = now + Duration::days(10 * 365);
+        let issuing = issuing
+            .signed_by(&issuing_key, &root, &root_key)
+            .expect("Signing the issuing CA");
+
+        MockCa {
+            root: root.der().clone(),
+            issuing: issuing.der().clone(),
+            pem_chain: format!("{}{}", root.pem(), issuing.pem()),
+            issuer: issuing,
+            issuing_key,
+            users: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The CA kept in the configured files, generated and written to them if there is none.
+    pub fn open(config: &CaConfig) -> Self {
+        let pem_chain = match std::fs::read_to_string(&config.cert) {
+            Ok(pem_chain) => pem_chain,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let ca = MockCa::generate();
+                std::fs::write(&config.cert, &ca.pem_chain)
+                    .and_then(|_| std::fs::write(&config.key, ca.issuing_key.serialize_pem()))
+                    .unwrap_or_else(|e| panic!("Writing CA {}: {e}", config.cert.display()));
+                return ca;
+            }
+            Err(e) => panic!("Reading CA cert {}: {e}", config.cert.display()),
+        };
+        let [root, issuing] = CertificateDer::pem_slice_iter(pem_chain.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_else(|e| panic!("Reading CA cert {}: {e}", config.cert.display()))
+            .try_into()
+            .unwrap_or_else(|_| {
+                panic!(
+                    "CA cert {} should hold the root and the issuing CA",
+                    config.cert.display()
+                )
+            });
+        let issuing_key = std::fs::read_to_string(&config.key)
+            .map_err(|e| e.to_string())
+            .and_then(|pem| KeyPair::from_pem(&pem).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| panic!("Reading CA key {}: {e}", config.key.display()));
+        let issuer = CertificateParams::from_ca_cert_der(&issuing)
+            .and_then(|params| params.self_signed(&issuing_key))
+            .unwrap_or_else(|e| panic!("Reading CA cert {}: {e}", config.cert.display()));
+
+        MockCa {
+            root,
+            issuing,
+            issuer,
+            issuing_key,
+            pem_chain,
+            users: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn root(&self) -> &CertificateDer<'static> {
+        &self.root
+    }
+
+    pub fn issuing(&self) -> &CertificateDer<'static> {
+        &self.issuing
+    }
+
+    /// The root and issuing CA as PEM, for RPs that want to trust the mock.
+    pub fn pem_chain(&self) -> String {
+        self.pem_chain.clone()
+    }
+
+    /// The certificate for the user, issued the first time the personal number signs.
+    pub fn user_certificate(&self, user: &UserCompletionData) -> Arc<UserCertificate> {
+        let mut users = self.users.lock().unwrap();
+        users
+            .entry(user.personal_number.clone())
+            .or_insert_with(|| Arc::new(self.issue(user)))
+            .clone()
+    }
+
+    fn issue(&self, user: &UserCompletionData) -> UserCertificate {
+        let now = OffsetDateTime::now_utc();
+        let key = KeyPair::generate().expect("Generating the user key");
+        let mut params = CertificateParams::default();
+        let mut distinguished_name = DistinguishedName::new();
+        distinguished_name.push(DnType::CountryName, "SE");
+        distinguished_name.push(
+            DnType::CustomDnType(SURNAME.to_vec()),
+            user.sur_name.as_str(),
+        );
+        distinguished_name.push(
+            DnType::CustomDnType(GIVEN_NAME.to_vec()),
+            user.given_name.as_str(),
+        );
+        distinguished_name.push(
+            DnType::CustomDnType(SERIAL_NUMBER.to_vec()),
+            user.personal_number.as_str(),
+        );
+        distinguished_name.push(DnType::CommonName, user.name.as_str());
+        params.distinguished_name = distinguished_name;
+        params.key_usages = vec![
+            KeyUsagePurpose::DigitalSignature,
+            KeyUsagePurpose::ContentCommitment,
+        ];
+        params.use_authority_key_identifier_extension = true;
+        params.not_before = now - Duration::days(1);
+        params.not_after = now + Duration::days(2 * 365);
+        let certificate = params
+            .signed_by(&key, &self.issuer, &self.issuing_key)
+            .expect("Signing the user certificate");
+        UserCertificate { certificate, key }
+    }
+}
+
+impl MockCa {
+    /// A DER OCSP response from the issuing CA saying the user certificate is good.
+    ///
+    /// The nonce is the SHA-1 of `signed`, which ties the response to the signature.
+    pub fn ocsp_response(&self, user: &UserCertificate, signed: &[u8]) -> Vec<u8> {
+        let issuing = X509Certificate::from_der(&self.issuing)
+            .expect("The issuing CA is valid DER")
+            .1;
+        let certificate = X509Certificate::from_der(user.certificate.der())
+            .expect("The user certificate is valid DER")
+            .1;
+        let issuer_name_hash = digest(&SHA1_FOR_LEGACY_USE_ONLY, issuing.subject().as_raw());
+        let issuer_key_hash = digest(&SHA1_FOR_LEGACY_USE_ONLY, self.issuing_key.public_key_raw());
+        let nonce = digest(&SHA1_FOR_LEGACY_USE_ONLY, signed);
+        let now = GeneralizedTime::from_datetime(
+            OffsetDateTime::now_utc().replace_nanosecond(0).unwrap(),
+        );
+
+        let response_data = yasna::construct_der(|writer| {
+            writer.write_sequence(|writer| {
+                writer.next().write_tagged(Tag::context(2), |writer| {
+                    writer.write_bytes(issuer_key_hash.as_ref())
+                });
+                writer.next().write_generalized_time(&now);
+                writer.next().write_sequence_of(|writer| {
+                    writer.next().write_sequence(|writer| {
+                        writer.next().write_sequence(|writer| {
+                            write_algorithm(writer.next(), SHA1, true);
+                            writer.next().write_bytes(issuer_name_hash.as_ref());
+                            writer.next().write_bytes(issuer_key_hash.as_ref());
+                            writer
+                                .next()
+                                .write_bigint_bytes(certificate.raw_serial(), true);
+                        });
+                        writer
+                            .next()
+                            .write_tagged_implicit(Tag::context(0), |writer| writer.write_null());
+                        writer.next().write_generalized_time(&now);
+                    });
+                });
+                writer.next().write_tagged(Tag::context(1), |writer| {
+                    writer.write_sequence(|writer| {
+                        writer.next().write_sequence(|writer| {
+                            writer
+                                .next()
+                                .write_oid(&ObjectIdentifier::from_slice(OCSP_NONCE));
+                            let value =
+                                yasna::construct_der(|writer| writer.write_bytes(nonce.as_ref()));
+                            writer.next().write_bytes(&value);
+                        });
+                    });
+                });
+            });
+        });
+        let signature = sign(
+            &self.issuing_key,
+            &ECDSA_P256_SHA256_ASN1_SIGNING,
+            &response_data,
+        );
+        let basic_response = yasna::construct_der(|writer| {
+            writer.write_sequence(|writer| {
+                writer.next().write_der(&response_data);
+                write_algorithm(writer.next(), ECDSA_WITH_SHA256, false);
+                writer
+                    .next()
+                    .write_bitvec_bytes(&signature, signature.len() * 8);
+            });
+        });
+
+        yasna::construct_der(|writer| {
+            writer.write_sequence(|writer| {
+                writer.next().write_enum(0);
+                writer.next().write_tagged(Tag::context(0), |writer| {
+                    writer.write_sequence(|writer| {
+                        writer
+                            .next()
+                            .write_oid(&ObjectIdentifier::from_slice(OCSP_BASIC));
+                        writer.next().write_bytes(&basic_response);
+                    });
+                });
+            });
+        })
+    }
+}
+
+impl UserCertificate {
+    /// ECDSA P-256 over SHA-256, as the raw `r || s` XML-DSig expects.
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        sign(&self.key, &ECDSA_P256_SHA256_FIXED_SIGNING, message)
+    }
+}
+
+fn sign(key: &KeyPair, algorithm: &'static EcdsaSigningAlgorithm, message: &[u8]) -> Vec<u8> {
+    let rng = SystemRandom::new();
+    let key = EcdsaKeyPair::from_pkcs8(algorithm, key.serialized_der(), &rng)
+        .expect("rcgen keys are PKCS#8");
+    key.sign(&rng, message)
+        .expect("Signing with an ECDSA key")
+        .as_ref()
+        .to_vec()
+}
+
+fn write_algorithm(writer: DERWriter, oid: &[u64], null_parameters: bool) {
+    writer.write_sequence(|writer| {
+        writer.next().write_oid(&ObjectIdentifier::from_slice(oid));
+        if null_parameters {
+            writer.next().write_null();
+        }
+    });
+}
+
+fn name(common_name: &str) -> DistinguishedName {
+    let mut name = DistinguishedName::new();
+    name.push(DnType::CountryName, "SE");
+    name.push(DnType::OrganizationName, "Mock BankID");
+    name.push(DnType::CommonName, common_name);
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_ASN1};
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn user() -> UserCompletionData {
+        UserCompletionData::new("201604064188".to_string(), "Test Testsson".to_string())
+    }
+
+    #[test]
+    fn open_keeps_the_ca_across_restarts() {
+        let path = std::env::temp_dir().join(format!("bankid-mock-ca-{}", Uuid::new_v4()));
+        let config = CaConfig {
+            cert: path.with_extension("pem"),
+            key: path.with_extension("key"),
+        };
+        let generated = MockCa::open(&config);
+        let restarted = MockCa::open(&config);
+        std::fs::remove_file(&config.cert).unwrap();
+        std::fs::remove_file(&config.key).unwrap();
+        assert_eq!(restarted.pem_chain(), generated.pem_chain());
+        assert_eq!(restarted.root(), generated.root());
+
+        let issuing = X509Certificate::from_der(restarted.issuing()).unwrap().1;
+        let user = restarted.user_certificate(&user());
+        let certificate = X509Certificate::from_der(user.certificate.der()).unwrap().1;
+        assert_eq!(certificate.issuer().as_raw(), issuing.subject().as_raw());
+        certificate
+            .verify_signature(Some(issuing.public_key()))
+            .unwrap();
+    }
+
+    #[test]
+    fn ocsp_response_says_the_user_certificate_is_good() {
+        let ca = MockCa::generate();
+        let user = ca.user_certificate(&user());
+        let response = ca.ocsp_response(&user, b"signed");
+
+        let basic_response = yasna::parse_der(&response, |reader| {
+            reader.read_sequence(|reader| {
+                assert_eq!(reader.next().read_enum()?, 0);
+                reader.next().read_tagged(Tag::context(0), |reader| {
+                    reader.read_sequence(|reader| {
+                        let oid = reader.next().read_oid()?;
+                        assert_eq!(oid, ObjectIdentifier::from_slice(OCSP_BASIC));
+                        reader.next().read_bytes()
+                    })
+                })
+            })
+        })
+        .unwrap();
+        let (response_data, signature) = yasna::parse_der(&basic_response, |reader| {
+            reader.read_sequence(|reader| {
+                let response_data = reader.next().read_der()?;
+                reader.next().read_der()?;
+                let (signature, _) = reader.next().read_bitvec_bytes()?;
+                Ok((response_data, signature))
+            })
+        })
+        .unwrap();
+        let issuing = X509Certificate::from_der(ca.issuing()).unwrap().1;
+        UnparsedPublicKey::new(
+            &ECDSA_P256_SHA256_ASN1,
+            &issuing.public_key().subject_public_key.data,
+        )
+        .verify(&response_data, &signature)
+        .unwrap();
+
+        let (serial, nonce) = yasna::parse_der(&response_data, |reader| {
+            reader.read_sequence(|reader| {
+                reader
+                    .next()
+                    .read_tagged(Tag::context(2), |reader| reader.read_bytes())?;
+                reader.next().read_generalized_time()?;
+                let serial = reader.next().read_sequence(|reader| {
+                    reader.next().read_sequence(|reader| {
+                        let serial = reader.next().read_sequence(|reader| {
+                            reader.next().read_der()?;
+                            reader.next().read_bytes()?;
+                            reader.next().read_bytes()?;
+                            reader.next().read_bigint_bytes()
+                        })?;
+                        // certStatus good
+                        reader
+                            .next()
+                            .read_tagged_implicit(Tag::context(0), |reader| reader.read_null())?;
+                        reader.next().read_generalized_time()?;
+                        Ok(serial)
+                    })
+                })?;
+                let nonce = reader.next().read_tagged(Tag::context(1), |reader| {
+                    reader.read_sequence(|reader| {
+                        reader.next().read_sequence(|reader| {
+                            let oid = reader.next().read_oid()?;
+                            assert_eq!(oid, ObjectIdentifier::from_slice(OCSP_NONCE));
+                            reader.next().read_bytes()
+                        })
+                    })
+                })?;
+                Ok((serial, nonce))
+            })
+        })
+        .unwrap();
+        let certificate = X509Certificate::from_der(user.certificate.der()).unwrap().1;
+        assert_eq!(serial, (certificate.serial.to_bytes_be(), true));
+        let nonce = yasna::parse_der(&nonce, |reader| reader.read_bytes()).unwrap();
+        assert_eq!(nonce, digest(&SHA1_FOR_LEGACY_USE_ONLY, b"signed").as_ref());
+    }
+}
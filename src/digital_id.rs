@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{OrderData, UserCompletionData};
+
+/// A mock of the digital ID card a user shows in the app, for RPs to check with `verify`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DigitalId {
+    /// What the QR code on the card holds.
+    pub qr_code: String,
+    pub user: UserCompletionData,
+    /// When the user identified themselves to open the card, with the order it was done in.
+    pub identified_at: OffsetDateTime,
+    pub order_ref: Uuid,
+}
+
+impl OrderData {
+    /// Opens a digital ID card for the user, which can be verified until the mock restarts.
+    pub fn issue_digital_id(&mut self, user: UserCompletionData) -> DigitalId {
+        let digital_id = DigitalId {
+            qr_code: format!("bankid.id.{}", Uuid::new_v4()),
+            user,
+            identified_at: OffsetDateTime::now_utc(),
+            order_ref: Uuid::new_v4(),
+        };
+        self.digital_ids
+            .insert(digital_id.qr_code.clone(), digital_id.clone());
+        digital_id
+    }
+
+    pub fn get_digital_id(&self, qr_code: &str) -> Option<&DigitalId> {
+        self.digital_ids.get(qr_code)
+    }
+}
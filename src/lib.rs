@@ -1,19 +1,36 @@
-use core::panic;
-use std::borrow::Borrow;
 use std::ops::Deref;
+use std::path::PathBuf;
 use std::{collections::HashMap, net::IpAddr, sync::Mutex};
 
 use serde::Deserialize;
 use serde::Serialize;
 
-use strum::EnumIter;
+use strum::{Display, EnumIter};
+use time::format_description::BorrowedFormatItem;
+use time::macros::format_description;
 use time::Duration;
-use time::OffsetDateTime;
+use time::{Date, OffsetDateTime};
 use uuid::Uuid;
 
 pub mod app;
+pub mod automation;
+#[cfg(feature = "ssr")]
+pub mod ca;
+pub mod digital_id;
 pub mod error_template;
+#[cfg(feature = "ssr")]
+pub mod qr;
+pub mod request;
+#[cfg(feature = "ssr")]
+pub mod signature;
+pub mod store;
+#[cfg(feature = "ssr")]
+pub mod tls;
 
+use automation::Automation;
+use digital_id::DigitalId;
+use request::{AuthRequest, CallInitiator};
+use store::{MemoryStore, OrderStore, StoreConfig};
 
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen]
@@ -56,57 +73,375 @@ impl Deref for ConfigState {
         &self.0
     }
 }
-#[derive(PartialEq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct Order {
     ip: IpAddr,
     order_time: OffsetDateTime,
+    order_type: OrderType,
+    request: AuthRequest,
+    /// Set for phone orders, which are confirmed over a call instead of a QR code.
+    call_initiator: Option<CallInitiator>,
+    /// The RP named by its client certificate, grouped by instead of the ip when set.
+    rp: Option<String>,
+    tokens: OrderTokens,
     data: OrderEnum,
+    /// When the order completed or failed, what the retention is counted from.
+    #[serde(default)]
+    finish_time: Option<OffsetDateTime>,
+    /// What the operator picked when completing the order.
+    #[serde(default)]
+    completion: Option<CompletionOptions>,
+    /// Who signed an order requiring MRTD, held until their passport has been scanned.
+    #[serde(default)]
+    mrtd_user: Option<UserCompletionData>,
+    /// Whether the passport scan of an order requiring MRTD succeeded.
+    #[serde(default)]
+    mrtd: Option<bool>,
+    /// Made on the first collect after completion, so later collects return the same document.
+    signature: Option<CompletionSignature>,
 }
-#[derive(PartialEq, Debug)]
+
+/// The per order secrets handed to the RP, used to start the app and animate the QR code.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderTokens {
+    pub auto_start_token: Uuid,
+    pub qr_start_token: Uuid,
+    pub qr_start_secret: Uuid,
+}
+
+impl OrderTokens {
+    fn generate() -> Self {
+        OrderTokens {
+            auto_start_token: Uuid::new_v4(),
+            qr_start_token: Uuid::new_v4(),
+            qr_start_secret: Uuid::new_v4(),
+        }
+    }
+}
+
+impl Order {
+    fn summary(&self, id: Uuid) -> OrderSummary {
+        OrderSummary {
+            id,
+            ip: self.ip,
+            order_time: self.order_time,
+            order_type: self.order_type,
+            call_initiator: self.call_initiator,
+            rp: self.rp.clone(),
+            mrtd_user: self.mrtd_user.clone(),
+            user_visible_data: self
+                .request
+                .user_visible_data
+                .as_deref()
+                .map(decode_base64_text),
+            user_non_visible_data: self
+                .request
+                .user_non_visible_data
+                .as_deref()
+                .map(decode_base64_text),
+            request: self.request.clone(),
+        }
+    }
+
+    fn finish(&mut self, data: OrderEnum) {
+        self.data = data;
+        self.finish_time = Some(OffsetDateTime::now_utc());
+    }
+
+    /// Completes the order as the user, unless the options break the order's requirement.
+    ///
+    /// Orders requiring MRTD move to userMrtd instead, until [`OrderData::scan_mrtd`].
+    fn complete(&mut self, user: UserCompletionData, options: CompletionOptions) {
+        if let Some(code) = self.rejection(&options) {
+            self.finish(OrderEnum::Failed(code));
+            return;
+        }
+        // Kept with the order, so every collect returns the same device and issue date.
+        let mut options = options;
+        options.device_ip.get_or_insert_with(|| {
+            self.request
+                .end_user_ip
+                .parse()
+                .unwrap_or_else(|_| random_device_ip())
+        });
+        options
+            .bank_id_issue_date
+            .get_or_insert_with(random_issue_date);
+        self.completion = Some(options);
+        if self.requires_mrtd() && self.mrtd.is_none() {
+            self.mrtd_user = Some(user);
+            self.data = OrderEnum::Pending(PendingData {
+                status: PendingCode::UserMrtd,
+            });
+            return;
+        }
+        self.finish(OrderEnum::Completed(user));
+    }
+
+    /// Why the order fails instead of completing with the options, if it does.
+    ///
+    /// A BankID the certificate policies leave out fails with certificateErr when only the
+    /// test or production environment differs, and otherwise with startFailed, as the app
+    /// finds no BankID to use. A high risk fails orders the RP flagged as risky.
+    fn rejection(&self, options: &CompletionOptions) -> Option<FailedHintCodes> {
+        let requirement = self.request.requirement.clone().unwrap_or_default();
+        if !requirement.allows(options.bank_id_type) {
+            return Some(
+                if requirement.allows(options.bank_id_type.in_other_environment()) {
+                    FailedHintCodes::CertificateErr
+                } else {
+                    FailedHintCodes::StartFailed
+                },
+            );
+        }
+        let flagged = self
+            .request
+            .risk_flags
+            .as_ref()
+            .is_some_and(|flags| !flags.is_empty());
+        if options.risk == RiskLevel::High && flagged {
+            return Some(FailedHintCodes::TransactionRiskBlocked);
+        }
+        None
+    }
+
+    fn scan_mrtd(&mut self, passed: bool) -> bool {
+        let Some(user) = self.mrtd_user.take() else {
+            return false;
+        };
+        self.mrtd = Some(passed);
+        self.finish(OrderEnum::Completed(user));
+        true
+    }
+
+    fn requires_mrtd(&self) -> bool {
+        self.request
+            .requirement
+            .as_ref()
+            .is_some_and(|r| r.mrtd == Some(true))
+    }
+
+    /// The ip of the simulated device, picked when the order completed.
+    ///
+    /// A completion without one uses the endUserIp, and a phone order, which has none,
+    /// gets the unspecified ip rather than the ip of the RP.
+    #[cfg(feature = "ssr")]
+    fn device_ip(&self) -> IpAddr {
+        self.completion
+            .and_then(|c| c.device_ip)
+            .or_else(|| self.request.end_user_ip.parse().ok())
+            .unwrap_or(IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED))
+    }
+
+    fn personal_number(&self) -> Option<&str> {
+        self.request
+            .requirement
+            .as_ref()
+            .and_then(|r| r.personal_number.as_deref())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum OrderType {
+    Auth,
+    Sign,
+    Payment,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub enum OrderEnum {
     Pending(PendingData),
     Completed(UserCompletionData),
-    Expired,
+    Failed(FailedHintCodes),
 }
 
 #[derive(Debug)]
 pub struct OrderData {
     data: HashMap<uuid::Uuid, Order>,
+    store: Box<dyn OrderStore>,
+    /// Kept in memory only, keyed by their QR code.
+    digital_ids: HashMap<String, DigitalId>,
 }
 use itertools::Itertools;
 impl OrderData {
     pub fn new() -> Self {
+        Self::with_store(Box::new(MemoryStore))
+    }
+
+    /// Starts with the orders left in the store, and saves every change back to it.
+    pub fn with_store(store: Box<dyn OrderStore>) -> Self {
         OrderData {
-            data: HashMap::new(),
+            data: store.load(),
+            store,
+            digital_ids: HashMap::new(),
         }
     }
 
-    pub fn insert_empty(&mut self, id: uuid::Uuid, ip: IpAddr) {
+    fn save(&mut self) {
+        self.store.save(&self.data);
+    }
+
+    pub fn insert_empty(
+        &mut self,
+        id: uuid::Uuid,
+        ip: IpAddr,
+        order_type: OrderType,
+        request: AuthRequest,
+        call_initiator: Option<CallInitiator>,
+        rp: Option<String>,
+    ) -> OrderTokens {
+        let tokens = OrderTokens::generate();
+        let status = match call_initiator {
+            Some(_) => PendingCode::UserCallConfirm,
+            None => PendingCode::Started,
+        };
         self.data.insert(
             id,
             Order {
                 ip,
                 order_time: OffsetDateTime::now_utc(),
-                data: OrderEnum::Pending(PendingData {
-                    status: PendingCode::Started,
-                }),
+                order_type,
+                request,
+                call_initiator,
+                rp,
+                tokens: tokens.clone(),
+                data: OrderEnum::Pending(PendingData { status }),
+                finish_time: None,
+                completion: None,
+                mrtd_user: None,
+                mrtd: None,
+                signature: None,
             },
         );
+        self.save();
+        tokens
+    }
+
+    /// Completes a pending order as the user, returns false if there was no pending order.
+    pub fn upgrade(
+        &mut self,
+        id: uuid::Uuid,
+        data: UserCompletionData,
+        options: CompletionOptions,
+    ) -> bool {
+        let Some(slot) = self
+            .data
+            .get_mut(&id)
+            .filter(|slot| matches!(slot.data, OrderEnum::Pending(_)))
+        else {
+            return false;
+        };
+        slot.complete(data, options);
+        self.save();
+        true
+    }
+
+    /// Completes an order waiting in userMrtd, with whether the passport scan succeeded.
+    /// Returns false if the order was not waiting for a scan.
+    pub fn scan_mrtd(&mut self, id: &Uuid, passed: bool) -> bool {
+        let scanned = self
+            .data
+            .get_mut(id)
+            .is_some_and(|order| order.scan_mrtd(passed));
+        if scanned {
+            self.save();
+        }
+        scanned
+    }
+
+    /// The `stepUp` of a completed order, for orders that required MRTD.
+    pub fn step_up(&self, id: &Uuid) -> Option<StepUp> {
+        self.data
+            .get(id)
+            .and_then(|o| o.mrtd)
+            .map(|mrtd| StepUp { mrtd })
     }
 
-    pub fn upgrade(&mut self, id: uuid::Uuid, data: UserCompletionData) {
-        let slot = self.data.get_mut(&id).unwrap();
-        slot.data = OrderEnum::Completed(data);
+    #[cfg(feature = "ssr")]
+    pub fn device(&self, id: &Uuid) -> Option<DeviceCompletionData> {
+        self.data
+            .get(id)
+            .map(|o| DeviceCompletionData::new(o.device_ip()))
+    }
+
+    pub fn bank_id_issue_date(&self, id: &Uuid) -> Option<Date> {
+        self.data
+            .get(id)
+            .and_then(|o| o.completion)
+            .and_then(|c| c.bank_id_issue_date)
     }
 
-    pub fn set_pending_status(&mut self, id: uuid::Uuid, status: PendingCode) {
-        let slot = self.data.get_mut(&id).unwrap();
-        match &mut slot.data {
-            OrderEnum::Pending(ref mut o) => o.status = status,
-            _ => panic!("Crash"),
+    /// The risk of a completed order, only when the RP asked for it with `returnRisk`.
+    pub fn returned_risk(&self, id: &Uuid) -> Option<RiskLevel> {
+        self.data
+            .get(id)
+            .filter(|o| o.request.return_risk == Some(true))
+            .and_then(|o| o.completion)
+            .map(|c| c.risk)
+    }
+
+    /// Moves a pending order to another status, returns false if there was no pending order.
+    pub fn set_pending_status(&mut self, id: uuid::Uuid, status: PendingCode) -> bool {
+        match self.data.get_mut(&id).map(|slot| &mut slot.data) {
+            Some(OrderEnum::Pending(o)) => {
+                o.status = status;
+                self.save();
+                true
+            }
+            _ => false,
         }
     }
 
+    /// Moves a pending order into a failed state, returns false if there was no pending order.
+    pub fn fail(&mut self, id: uuid::Uuid, code: FailedHintCodes) -> bool {
+        match self.data.get_mut(&id) {
+            Some(slot) if matches!(slot.data, OrderEnum::Pending(_)) => {
+                slot.finish(OrderEnum::Failed(code));
+                self.save();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Cancels every pending order for the personal number, returns true if there were any.
+    pub fn cancel_pending_for(&mut self, personal_number: &str) -> bool {
+        let mut cancelled = false;
+        self.data
+            .values_mut()
+            .filter(|o| matches!(o.data, OrderEnum::Pending(_)))
+            .filter(|o| o.personal_number() == Some(personal_number))
+            .for_each(|o| {
+                o.finish(OrderEnum::Failed(FailedHintCodes::Cancelled));
+                cancelled = true;
+            });
+        if cancelled {
+            self.save();
+        }
+        cancelled
+    }
+
+    /// The signature of a completed order, signed with `sign` the first time it is asked for.
+    pub fn signature(
+        &mut self,
+        id: &Uuid,
+        sign: impl FnOnce(&OrderSummary, &UserCompletionData) -> CompletionSignature,
+    ) -> Option<CompletionSignature> {
+        let order = self.data.get_mut(id)?;
+        let OrderEnum::Completed(user) = &order.data else {
+            return None;
+        };
+        if order.signature.is_none() {
+            order.signature = Some(sign(&order.summary(*id), user));
+            let signature = order.signature.clone();
+            self.save();
+            return signature;
+        }
+        order.signature.clone()
+    }
+
     pub fn get(&self, id: &uuid::Uuid) -> Option<&OrderEnum> {
         self.data.get(&id).map(|p| &p.data)
     }
@@ -114,37 +449,123 @@ impl OrderData {
         self.data
             .iter()
             .filter(|o| matches!(o.1.data, OrderEnum::Pending(_)))
+            .filter(|o| o.1.rp.is_none())
             .map(|o| &o.1.ip)
             .cloned()
             .unique()
             .collect()
     }
 
-    pub fn remove_old(&mut self) -> u32 {
+    /// Fails orders pending for longer than `pending_timeout` with expiredTransaction, and
+    /// forgets orders that finished more than `retention` ago. Returns how many changed.
+    pub fn remove_old(&mut self, pending_timeout: Duration, retention: Duration) -> usize {
         let now = OffsetDateTime::now_utc();
-        let condition = |a: (&Uuid, &Order)| {
-            matches!(a.1.data, OrderEnum::Pending(_))
-                && a.1.order_time.saturating_add(Duration::seconds(50)) < now
-        };
-        let old_count = self.data.iter().filter(|(a, b)| condition((a, b))).count();
+        let before = self.data.len();
+        self.data.retain(|_, o| {
+            if matches!(o.data, OrderEnum::Pending(_)) {
+                return true;
+            }
+            // Finished orders without a finish time count from when they were made.
+            let finished = o.finish_time.unwrap_or(o.order_time);
+            finished.saturating_add(retention) >= now
+        });
+        let mut changed = before - self.data.len();
 
         self.data
-            .iter_mut()
-            .filter(|(a, b)| condition((a, b.borrow())))
-            .for_each(|f| f.1.data = OrderEnum::Expired);
+            .values_mut()
+            .filter(|o| matches!(o.data, OrderEnum::Pending(_)))
+            .filter(|o| o.order_time.saturating_add(pending_timeout) < now)
+            .for_each(|o| {
+                o.finish(OrderEnum::Failed(FailedHintCodes::ExpiredTransaction));
+                changed += 1;
+            });
 
-        let new_count = self.data.iter().filter(|(a, b)| condition((a, b))).count();
+        if changed > 0 {
+            self.save();
+        }
+        changed
+    }
+    /// The RPs identified by client certificate with pending orders.
+    pub fn get_rps(&self) -> Vec<String> {
+        self.data
+            .values()
+            .filter(|o| matches!(o.data, OrderEnum::Pending(_)))
+            .filter_map(|o| o.rp.clone())
+            .unique()
+            .collect()
+    }
 
-        return (old_count - new_count).try_into().unwrap();
+    pub fn get_all(&self, ip: &IpAddr) -> Vec<OrderSummary> {
+        self.data
+            .iter()
+            .filter(|o| matches!(o.1.data, OrderEnum::Pending(_)))
+            .filter(|o| &o.1.ip == ip && o.1.rp.is_none())
+            .map(|(id, o)| o.summary(*id))
+            .collect()
     }
-    pub fn get_all(&self, ip: &IpAddr) -> Vec<(OffsetDateTime, Uuid)> {
+
+    pub fn get_all_for_rp(&self, rp: &str) -> Vec<OrderSummary> {
         self.data
             .iter()
             .filter(|o| matches!(o.1.data, OrderEnum::Pending(_)))
-            .filter(|o| &o.1.ip == ip)
-            .map(|o| (o.1.order_time.clone(), o.0.clone()))
+            .filter(|o| o.1.rp.as_deref() == Some(rp))
+            .map(|(id, o)| o.summary(*id))
             .collect()
     }
+
+    pub fn get_with_state(&self, id: &Uuid) -> Option<(OrderSummary, OrderEnum)> {
+        self.data.get(id).map(|o| (o.summary(*id), o.data.clone()))
+    }
+
+    /// Every order still kept, whatever its state, oldest first.
+    pub fn get_all_with_state(&self) -> Vec<(OrderSummary, OrderEnum)> {
+        self.data
+            .iter()
+            .sorted_by_key(|o| o.1.order_time)
+            .map(|(id, o)| (o.summary(*id), o.data.clone()))
+            .collect()
+    }
+
+    /// The pending order started with the autoStartToken, as opened by the mock app.
+    pub fn get_by_auto_start_token(&self, token: &Uuid) -> Option<OrderSummary> {
+        self.data
+            .iter()
+            .filter(|o| matches!(o.1.data, OrderEnum::Pending(_)))
+            .find(|o| &o.1.tokens.auto_start_token == token)
+            .map(|(id, o)| o.summary(*id))
+    }
+}
+
+/// What the UI needs to know to render a pending order.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderSummary {
+    pub id: Uuid,
+    pub ip: IpAddr,
+    pub order_time: OffsetDateTime,
+    pub order_type: OrderType,
+    pub call_initiator: Option<CallInitiator>,
+    pub rp: Option<String>,
+    /// Set while the order waits for the user's passport to be scanned.
+    pub mrtd_user: Option<UserCompletionData>,
+    pub request: AuthRequest,
+    pub user_visible_data: Option<String>,
+    pub user_non_visible_data: Option<String>,
+}
+
+/// What the user signed, and the OCSP response saying their certificate was valid at the time.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletionSignature {
+    pub signature: String,
+    pub ocsp_response: String,
+}
+
+fn decode_base64_text(value: &str) -> String {
+    use base64::Engine;
+    base64::engine::general_purpose::STANDARD
+        .decode(value)
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .unwrap_or_else(|_| value.to_string())
 }
 
 impl Clone for Orders {
@@ -158,7 +579,7 @@ impl Clone for ConfigState {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PendingData {
     pub status: PendingCode,
 }
@@ -173,7 +594,116 @@ pub enum PendingCode {
     NoClient,
     OutstandingTransaction,
 }
-#[derive(Serialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, EnumIter, Display)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum FailedHintCodes {
+    UserCancel,
+    Cancelled,
+    StartFailed,
+    ExpiredTransaction,
+    CertificateErr,
+    UserDeclinedCall,
+    NotSupportedByUserApp,
+    TransactionRiskBlocked,
+}
+/// The extra checks the user went through, returned as `completionData.stepUp`.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct StepUp {
+    /// Whether the user's passport was scanned and matched them.
+    pub mrtd: bool,
+}
+
+/// What the operator picks about the simulated user when completing an order.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletionOptions {
+    #[serde(default)]
+    pub risk: RiskLevel,
+    #[serde(default)]
+    pub bank_id_type: BankIdType,
+    /// The ip of the user's device, the RP's endUserIp when left out, or a random private
+    /// ip for phone orders, which have none.
+    #[serde(default)]
+    pub device_ip: Option<IpAddr>,
+    /// When the user's BankID was issued, a random date in the last years when left out.
+    #[serde(default, with = "issue_date::option")]
+    pub bank_id_issue_date: Option<Date>,
+}
+
+/// How `bankIdIssueDate` is written, like 2023-01-01.
+pub const ISSUE_DATE_FORMAT: &[BorrowedFormatItem<'_>] =
+    format_description!("[year]-[month]-[day]");
+
+time::serde::format_description!(pub issue_date, Date, ISSUE_DATE_FORMAT);
+
+fn random_device_ip() -> IpAddr {
+    let [a, b, c] = rand::random::<[u8; 3]>();
+    IpAddr::V4(std::net::Ipv4Addr::new(10, a, b, c))
+}
+
+fn random_issue_date() -> Date {
+    use rand::Rng;
+    // BankIDs are valid for up to eight years.
+    let days = rand::rng().random_range(1..8 * 365);
+    OffsetDateTime::now_utc().date() - Duration::days(days)
+}
+
+/// The kind of BankID the simulated user has, each issued under its own certificate policy.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default, EnumIter, Display)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum BankIdType {
+    #[default]
+    Mobile,
+    File,
+    Card,
+    TestMobile,
+    TestFile,
+    TestCard,
+}
+
+impl BankIdType {
+    pub fn certificate_policy(&self) -> &'static str {
+        match self {
+            BankIdType::Mobile => "1.2.752.78.1.5",
+            BankIdType::File => "1.2.752.78.1.1",
+            BankIdType::Card => "1.2.752.78.1.2",
+            BankIdType::TestMobile => "1.2.3.4.25",
+            BankIdType::TestFile => "1.2.3.4.5",
+            BankIdType::TestCard => "1.2.3.4.10",
+        }
+    }
+
+    pub fn is_card(&self) -> bool {
+        matches!(self, BankIdType::Card | BankIdType::TestCard)
+    }
+
+    /// The same kind of BankID, issued for test instead of production or the other way around.
+    pub fn in_other_environment(&self) -> Self {
+        match self {
+            BankIdType::Mobile => BankIdType::TestMobile,
+            BankIdType::File => BankIdType::TestFile,
+            BankIdType::Card => BankIdType::TestCard,
+            BankIdType::TestMobile => BankIdType::Mobile,
+            BankIdType::TestFile => BankIdType::File,
+            BankIdType::TestCard => BankIdType::Card,
+        }
+    }
+}
+
+/// How risky BankID judged the order, returned as `completionData.risk` on `returnRisk`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default, EnumIter, Display)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum RiskLevel {
+    #[default]
+    Low,
+    Moderate,
+    High,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct UserCompletionData {
     pub personal_number: String,
@@ -181,10 +711,42 @@ pub struct UserCompletionData {
     pub given_name: String,
     pub sur_name: String,
 }
+
+impl UserCompletionData {
+    /// Splits the given name and surname out of the full name.
+    pub fn new(personal_number: String, name: String) -> Self {
+        let mut split = name.split_whitespace();
+        let given_name = split.next().unwrap_or("").to_string();
+        let sur_name = split.last().unwrap_or("").to_string();
+        UserCompletionData {
+            personal_number,
+            name,
+            given_name,
+            sur_name,
+        }
+    }
+}
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeviceCompletionData {
-    pub ip_adress: String,
+    pub ip_address: String,
+    /// The unique hardware identifier, made from the ip so each device keeps its own.
+    pub uhi: String,
+}
+
+#[cfg(feature = "ssr")]
+impl DeviceCompletionData {
+    pub fn new(ip: IpAddr) -> Self {
+        use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        use base64::Engine;
+        use sha2::{Digest, Sha256};
+
+        let hash = Sha256::digest(format!("uhi:{ip}"));
+        DeviceCompletionData {
+            ip_address: ip.to_string(),
+            uhi: URL_SAFE_NO_PAD.encode(&hash[..20]),
+        }
+    }
 }
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "kebab-case")]
@@ -193,6 +755,65 @@ pub struct Config {
     pub quick_users: Option<Vec<QuickUser>>,
     pub first_names: Option<Vec<String>>,
     pub last_names: Option<Vec<String>>,
+    /// Answers every RP request with `maintenance` when set.
+    pub maintenance: Option<bool>,
+    /// Where the ip orders are grouped by comes from, defaults to the socket.
+    pub ip_source: Option<IpSource>,
+    /// Serves https instead of http when set.
+    pub tls: Option<TlsConfig>,
+    /// Where orders are kept between restarts, only in memory by default.
+    pub store: Option<StoreConfig>,
+    /// Seconds an order may be pending before it fails with expiredTransaction.
+    pub pending_timeout: Option<u32>,
+    /// Seconds a completed or failed order can still be collected.
+    pub retention: Option<u32>,
+    /// Completes or fails orders from some RPs without using the UI.
+    pub automations: Option<Vec<Automation>>,
+    /// Where the mock CA is kept between restarts, a new one every start by default.
+    pub ca: Option<CaConfig>,
+}
+
+impl Config {
+    /// Three minutes unless configured, like the real service.
+    pub fn pending_timeout(&self) -> Duration {
+        Duration::seconds(self.pending_timeout.unwrap_or(3 * 60).into())
+    }
+
+    /// 30 days unless configured, like the real service.
+    pub fn retention(&self) -> Duration {
+        Duration::seconds(self.retention.unwrap_or(30 * 24 * 60 * 60).into())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct TlsConfig {
+    /// PEM certificate chain and key to serve, a self-signed one for localhost if left out.
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+    /// PEM client certificates allowed to call the RP API.
+    pub client_certs: Option<Vec<PathBuf>>,
+    /// PEM CA whose client certificates are allowed to call the RP API.
+    pub client_ca: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct CaConfig {
+    /// PEM root and issuing CA, generated and written here on the first start.
+    pub cert: PathBuf,
+    /// PEM key of the issuing CA, written along with `cert`.
+    pub key: PathBuf,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum IpSource {
+    #[default]
+    Socket,
+    EndUserIp,
+    XForwardedFor,
+    XRealIp,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -207,6 +828,24 @@ pub struct QuickUser {
     pub label: String,
     pub ssn: String,
     pub name: String,
+    /// The risk orders completed as this user get, unless picked otherwise.
+    pub risk: Option<RiskLevel>,
+    /// The kind of BankID this user has, mobile unless set.
+    pub bank_id_type: Option<BankIdType>,
+    /// When this user's BankID was issued, a random date for each order unless set.
+    #[serde(default, with = "issue_date::option")]
+    pub bank_id_issue_date: Option<Date>,
+}
+
+impl QuickUser {
+    pub fn completion_options(&self) -> CompletionOptions {
+        CompletionOptions {
+            risk: self.risk.unwrap_or_default(),
+            bank_id_type: self.bank_id_type.unwrap_or_default(),
+            device_ip: None,
+            bank_id_issue_date: self.bank_id_issue_date,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
@@ -217,3 +856,339 @@ impl Default for MyDateTime {
         Self(OffsetDateTime::now_utc())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order_data(request: AuthRequest) -> (OrderData, Uuid) {
+        let mut orders = OrderData::new();
+        let id = Uuid::new_v4();
+        orders.insert_empty(
+            id,
+            "127.0.0.1".parse().unwrap(),
+            OrderType::Auth,
+            request,
+            None,
+            None,
+        );
+        (orders, id)
+    }
+
+    #[test]
+    fn remove_old_expires_pending_and_evicts_finished_orders() {
+        let (mut orders, pending) = order_data(AuthRequest::default());
+        let (finished_orders, finished) = order_data(AuthRequest::default());
+        orders.data.extend(finished_orders.data);
+        let hour_ago = OffsetDateTime::now_utc() - Duration::hours(1);
+        orders.data.get_mut(&pending).unwrap().order_time = hour_ago;
+        let order = orders.data.get_mut(&finished).unwrap();
+        order.order_time = hour_ago;
+        order.finish(OrderEnum::Failed(FailedHintCodes::UserCancel));
+
+        assert_eq!(
+            orders.remove_old(Duration::minutes(3), Duration::minutes(30)),
+            1
+        );
+        assert_eq!(
+            orders.get(&pending),
+            Some(&OrderEnum::Failed(FailedHintCodes::ExpiredTransaction))
+        );
+        assert!(orders.get(&finished).is_some());
+
+        orders.data.get_mut(&finished).unwrap().finish_time = Some(hour_ago);
+        assert_eq!(
+            orders.remove_old(Duration::minutes(3), Duration::minutes(30)),
+            1
+        );
+        assert!(orders.get(&finished).is_none());
+        assert!(orders.get(&pending).is_some());
+    }
+
+    #[test]
+    fn remove_old_evicts_finished_orders_saved_without_finish_time() {
+        let (mut orders, id) = order_data(AuthRequest::default());
+        let order = orders.data.get_mut(&id).unwrap();
+        order.order_time = OffsetDateTime::now_utc() - Duration::days(31);
+        order.data = OrderEnum::Failed(FailedHintCodes::UserCancel);
+        order.finish_time = None;
+
+        assert_eq!(
+            orders.remove_old(Duration::minutes(3), Duration::days(30)),
+            1
+        );
+        assert!(orders.get(&id).is_none());
+    }
+
+    fn user() -> UserCompletionData {
+        UserCompletionData::new("201604064188".to_string(), "Test Testsson".to_string())
+    }
+
+    fn complete(request: AuthRequest, options: CompletionOptions) -> OrderEnum {
+        let (mut orders, id) = order_data(request);
+        orders.upgrade(id, user(), options);
+        orders.get(&id).unwrap().clone()
+    }
+
+    #[test]
+    fn high_risk_blocks_only_flagged_orders() {
+        let flagged = AuthRequest {
+            risk_flags: Some(vec![request::RiskFlag::NewRecipient]),
+            ..Default::default()
+        };
+        let high = CompletionOptions {
+            risk: RiskLevel::High,
+            ..Default::default()
+        };
+        assert_eq!(
+            complete(flagged.clone(), high),
+            OrderEnum::Failed(FailedHintCodes::TransactionRiskBlocked)
+        );
+        assert_eq!(
+            complete(flagged, CompletionOptions::default()),
+            OrderEnum::Completed(user())
+        );
+        assert_eq!(
+            complete(AuthRequest::default(), high),
+            OrderEnum::Completed(user())
+        );
+    }
+
+    #[test]
+    fn bank_id_types_outside_the_policies_fail() {
+        let test_mobile_only = AuthRequest {
+            requirement: Some(request::Requirement {
+                certificate_policies: Some(vec!["1.2.3.4.25".to_string()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let complete_as = |bank_id_type| {
+            complete(
+                test_mobile_only.clone(),
+                CompletionOptions {
+                    bank_id_type,
+                    ..Default::default()
+                },
+            )
+        };
+        assert_eq!(
+            complete_as(BankIdType::TestMobile),
+            OrderEnum::Completed(user())
+        );
+        assert_eq!(
+            complete_as(BankIdType::Mobile),
+            OrderEnum::Failed(FailedHintCodes::CertificateErr)
+        );
+        assert_eq!(
+            complete_as(BankIdType::TestFile),
+            OrderEnum::Failed(FailedHintCodes::StartFailed)
+        );
+    }
+
+    #[test]
+    fn policy_failures_come_before_risk() {
+        let request = AuthRequest {
+            requirement: Some(request::Requirement {
+                certificate_policies: Some(vec!["1.2.752.78.1.5".to_string()]),
+                ..Default::default()
+            }),
+            risk_flags: Some(vec![request::RiskFlag::LargeAmount]),
+            ..Default::default()
+        };
+        let options = CompletionOptions {
+            risk: RiskLevel::High,
+            bank_id_type: BankIdType::TestMobile,
+            ..Default::default()
+        };
+        assert_eq!(
+            complete(request, options),
+            OrderEnum::Failed(FailedHintCodes::CertificateErr)
+        );
+    }
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn uhi_is_stable_per_device_ip() {
+        let device = DeviceCompletionData::new("10.1.2.3".parse().unwrap());
+        assert_eq!(device.ip_address, "10.1.2.3");
+        assert_eq!(
+            device.uhi,
+            DeviceCompletionData::new("10.1.2.3".parse().unwrap()).uhi
+        );
+        assert_ne!(
+            device.uhi,
+            DeviceCompletionData::new("10.1.2.4".parse().unwrap()).uhi
+        );
+    }
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn device_ip_defaults_to_end_user_ip() {
+        let request = AuthRequest {
+            end_user_ip: "10.1.2.3".to_string(),
+            ..Default::default()
+        };
+        let (mut orders, id) = order_data(request.clone());
+        orders.upgrade(id, user(), CompletionOptions::default());
+        assert_eq!(orders.device(&id).unwrap().ip_address, "10.1.2.3");
+
+        let (mut orders, id) = order_data(request);
+        let options = CompletionOptions {
+            device_ip: Some("192.168.0.9".parse().unwrap()),
+            ..Default::default()
+        };
+        orders.upgrade(id, user(), options);
+        assert_eq!(orders.device(&id).unwrap().ip_address, "192.168.0.9");
+    }
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn phone_orders_get_their_own_device_ip() {
+        let (mut orders, id) = order_data(AuthRequest::default());
+        orders.upgrade(id, user(), CompletionOptions::default());
+        let device = orders.device(&id).unwrap();
+        assert_ne!(device.ip_address, "127.0.0.1");
+        assert!(device.ip_address.starts_with("10."));
+        // The ip is kept, so later collects answer with the same device.
+        assert_eq!(orders.device(&id).unwrap().uhi, device.uhi);
+    }
+
+    #[test]
+    fn issue_date_is_picked_once_per_order() {
+        let (mut orders, id) = order_data(AuthRequest::default());
+        orders.upgrade(id, user(), CompletionOptions::default());
+        let issued = orders.bank_id_issue_date(&id).unwrap();
+        assert!(issued < OffsetDateTime::now_utc().date());
+        assert_eq!(orders.bank_id_issue_date(&id), Some(issued));
+
+        let (mut orders, id) = order_data(AuthRequest::default());
+        let date = Date::from_calendar_date(2020, time::Month::February, 3).unwrap();
+        let options = CompletionOptions {
+            bank_id_issue_date: Some(date),
+            ..Default::default()
+        };
+        orders.upgrade(id, user(), options);
+        assert_eq!(orders.bank_id_issue_date(&id), Some(date));
+    }
+
+    #[test]
+    fn updating_expired_or_evicted_orders_does_not_panic() {
+        let (mut orders, id) = order_data(AuthRequest::default());
+        orders.data.get_mut(&id).unwrap().order_time -= Duration::hours(1);
+        orders.remove_old(Duration::minutes(3), Duration::days(30));
+        assert!(!orders.set_pending_status(id, PendingCode::UserSign));
+        assert_eq!(
+            orders.get(&id),
+            Some(&OrderEnum::Failed(FailedHintCodes::ExpiredTransaction))
+        );
+
+        let evicted = Uuid::new_v4();
+        assert!(!orders.set_pending_status(evicted, PendingCode::UserSign));
+        assert!(!orders.upgrade(evicted, user(), CompletionOptions::default()));
+    }
+
+    #[test]
+    fn finished_orders_are_not_completed_again() {
+        let (mut orders, id) = order_data(AuthRequest::default());
+        assert!(orders.fail(id, FailedHintCodes::Cancelled));
+        assert!(!orders.upgrade(id, user(), CompletionOptions::default()));
+        assert_eq!(
+            orders.get(&id),
+            Some(&OrderEnum::Failed(FailedHintCodes::Cancelled))
+        );
+    }
+
+    #[test]
+    fn file_store_keeps_orders_across_restarts() {
+        let path = std::env::temp_dir().join(format!("bankid-mock-{}.json", Uuid::new_v4()));
+        let (mut orders, id) = order_data(AuthRequest {
+            end_user_ip: "10.1.2.3".to_string(),
+            ..Default::default()
+        });
+        orders.store = Box::new(store::FileStore::new(path.clone()));
+        let options = CompletionOptions {
+            risk: RiskLevel::Moderate,
+            bank_id_type: BankIdType::TestCard,
+            ..Default::default()
+        };
+        orders.upgrade(id, user(), options);
+
+        let restarted = OrderData::with_store(Box::new(store::FileStore::new(path.clone())));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(restarted.data, orders.data);
+        assert_eq!(restarted.get(&id), Some(&OrderEnum::Completed(user())));
+    }
+
+    #[test]
+    fn completion_options_default_missing_fields() {
+        let options: CompletionOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(options, CompletionOptions::default());
+    }
+
+    fn mrtd_order() -> (OrderData, Uuid) {
+        order_data(AuthRequest {
+            requirement: Some(request::Requirement {
+                mrtd: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn mrtd_orders_wait_for_the_passport_scan() {
+        for passed in [true, false] {
+            let (mut orders, id) = mrtd_order();
+            assert!(!orders.scan_mrtd(&id, passed));
+            orders.upgrade(id, user(), CompletionOptions::default());
+            assert_eq!(
+                orders.get(&id),
+                Some(&OrderEnum::Pending(PendingData {
+                    status: PendingCode::UserMrtd
+                }))
+            );
+            assert_eq!(orders.step_up(&id), None);
+
+            assert!(orders.scan_mrtd(&id, passed));
+            assert_eq!(orders.get(&id), Some(&OrderEnum::Completed(user())));
+            assert_eq!(orders.step_up(&id), Some(StepUp { mrtd: passed }));
+            assert!(!orders.scan_mrtd(&id, passed));
+        }
+    }
+
+    #[test]
+    fn orders_without_mrtd_have_no_step_up() {
+        let (mut orders, id) = order_data(AuthRequest::default());
+        orders.upgrade(id, user(), CompletionOptions::default());
+        assert_eq!(orders.get(&id), Some(&OrderEnum::Completed(user())));
+        assert_eq!(orders.step_up(&id), None);
+    }
+
+    #[test]
+    fn a_second_order_for_the_personal_number_cancels_the_pending_one() {
+        let for_persona = || AuthRequest {
+            requirement: Some(request::Requirement {
+                personal_number: Some("201604064188".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let (mut orders, pending) = order_data(for_persona());
+        let (completed_orders, completed) = order_data(for_persona());
+        let (other_orders, other) = order_data(AuthRequest::default());
+        orders.data.extend(completed_orders.data);
+        orders.data.extend(other_orders.data);
+        orders.upgrade(completed, user(), CompletionOptions::default());
+
+        assert!(orders.cancel_pending_for("201604064188"));
+        assert_eq!(
+            orders.get(&pending),
+            Some(&OrderEnum::Failed(FailedHintCodes::Cancelled))
+        );
+        assert_eq!(orders.get(&completed), Some(&OrderEnum::Completed(user())));
+        assert!(matches!(orders.get(&other), Some(OrderEnum::Pending(_))));
+
+        assert!(!orders.cancel_pending_for("201604064188"));
+    }
+}
@@ -8,13 +8,20 @@ use axum::{
 #[cfg(feature = "ssr")]
 use axum::{routing::get, Router};
 #[cfg(feature = "ssr")]
-use bankid_mock::Config;
+use bankid_mock::ca::MockCa;
+#[cfg(feature = "ssr")]
+use bankid_mock::request::{AuthRequest, CallInitiator, PhoneAuthRequest};
+#[cfg(feature = "ssr")]
+use bankid_mock::tls::{AcceptedClients, ClientCertificate};
 #[cfg(feature = "ssr")]
 use bankid_mock::OrderData;
 use bankid_mock::{
-    app::App, ConfigState, DeviceCompletionData, Orders, PendingCode, UserCompletionData,
+    app::App, BankIdType, CompletionOptions, ConfigState, DeviceCompletionData, FailedHintCodes,
+    OrderType, Orders, PendingCode, RiskLevel, UserCompletionData,
 };
 #[cfg(feature = "ssr")]
+use bankid_mock::{Config, IpSource};
+#[cfg(feature = "ssr")]
 use config::get_configuration;
 #[cfg(feature = "ssr")]
 use http::HeaderMap;
@@ -109,14 +116,39 @@ async fn main() {
 
     let toml_str = std::fs::read_to_string("config.toml").unwrap_or(String::default());
 
-    let orders = Orders::new(OrderData::new());
     let decoded: Config = toml::from_str(&toml_str).unwrap();
+    if let Err(e) = decoded.check_automations() {
+        panic!("{e}");
+    }
+    let orders = Orders::new(OrderData::with_store(
+        decoded.store.clone().unwrap_or_default().open(),
+    ));
+    let ca = std::sync::Arc::new(
+        decoded
+            .ca
+            .as_ref()
+            .map(MockCa::open)
+            .unwrap_or_else(MockCa::generate),
+    );
+    let tls_acceptor = decoded.tls.as_ref().map(bankid_mock::tls::acceptor);
+    let clients = decoded
+        .tls
+        .as_ref()
+        .and_then(AcceptedClients::load)
+        .map(std::sync::Arc::new);
+    let client_ip_source = match decoded.ip_source.unwrap_or_default() {
+        IpSource::Socket | IpSource::EndUserIp => ClientIpSource::ConnectInfo,
+        IpSource::XForwardedFor => ClientIpSource::RightmostXForwardedFor,
+        IpSource::XRealIp => ClientIpSource::XRealIp,
+    };
     let mut state = AppState {
         options: leptos_options.clone(),
         routes: None,
         orders: orders.clone(),
         config: ConfigState::new(decoded),
         server_signals: server_signals.clone(),
+        ca,
+        clients,
     };
     // Setting get_configuration(None) means we'll be using cargo-leptos's env values
     // For deployment these variables are:
@@ -126,14 +158,17 @@ async fn main() {
     let addr = leptos_options.site_addr;
     let routes = generate_route_list(App);
     state.routes = Some(routes.clone());
+    tokio::spawn(update_orders(state.clone()));
     let state2 = state.clone();
     let app = Router::new()
         .route(
             "/api/{*fn_name}",
             get(server_fn_handler).post(server_fn_handler),
         )
-        .route("/rp/v6.0/auth", axum::routing::post(auth))
-        .route("/rp/v6.0/collect", axum::routing::post(collect))
+        .nest("/rp/v6.0", rp_routes(state.clone()))
+        .route("/mock/qr/verify", axum::routing::post(verify_qr))
+        .route("/mock/ca.pem", get(ca_certificates))
+        .nest("/admin", admin_routes())
         .leptos_routes_with_handler(routes, get(leptos_routes_handler))
         .fallback(leptos_axum::file_and_error_handler_with_context::<
             AppState,
@@ -147,92 +182,664 @@ async fn main() {
             },
             shell,
         ))
-        .layer(ClientIpSource::ConnectInfo.into_extension())
+        .layer(client_ip_source.into_extension())
         .with_state(state);
     // run our app with hyper
     // `axum::Server` is a re-export of `hyper::Server`
-    leptos::logging::log!("listening on http://{}", &addr);
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .await
-    .unwrap();
+    if let Some(acceptor) = tls_acceptor {
+        leptos::logging::log!("listening on https://{}", &addr);
+        bankid_mock::tls::serve(listener, acceptor, app).await;
+    } else {
+        leptos::logging::log!("listening on http://{}", &addr);
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap();
+    }
+}
+
+/// The relying party API, answering every failure the way the real service does.
+#[cfg(feature = "ssr")]
+fn rp_routes(state: AppState) -> Router<AppState> {
+    use axum::routing::post;
+
+    Router::new()
+        .route("/auth", post(auth))
+        .route("/sign", post(sign))
+        .route("/payment", post(payment))
+        .route("/phone/auth", post(phone_auth))
+        .route("/phone/sign", post(phone_sign))
+        .route("/collect", post(collect))
+        .route("/cancel", post(cancel))
+        .route("/verify", post(verify))
+        .fallback(|| async { RpError::NotFound })
+        .method_not_allowed_fallback(|| async { RpError::MethodNotAllowed })
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            maintenance,
+        ))
+        .layer(axum::middleware::from_fn_with_state(state, authenticate))
+}
+
+/// Turns away RPs without an accepted client certificate when client certificates are configured.
+#[cfg(feature = "ssr")]
+async fn authenticate(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    request: Request,
+    next: axum::middleware::Next,
+) -> AxumResponse {
+    if let Some(clients) = &state.clients {
+        let accepted = request
+            .extensions()
+            .get::<ClientCertificate>()
+            .is_some_and(|certificate| clients.accepts(certificate));
+        if !accepted {
+            return RpError::Unauthorized("No accepted client certificate".to_string())
+                .into_response();
+        }
+    }
+    next.run(request).await
+}
+
+#[cfg(feature = "ssr")]
+async fn maintenance(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    request: Request,
+    next: axum::middleware::Next,
+) -> AxumResponse {
+    if state.config.maintenance.unwrap_or_default() {
+        return RpError::Maintenance.into_response();
+    }
+    next.run(request).await
 }
 
 #[axum::debug_handler]
 #[cfg(feature = "ssr")]
 async fn auth(
     axum::extract::State(state): axum::extract::State<AppState>,
-    insecure_ip: axum_client_ip::ClientIp,
-) -> Json<AuthResponse> {
-    use leptos_ws::ReadOnlySignal;
+    client: RpClient,
+    RpJson(request): RpJson<AuthRequest>,
+) -> Result<Json<AuthResponse>, RpError> {
+    request
+        .validate(OrderType::Auth)
+        .map_err(RpError::InvalidParameters)?;
+    Ok(Json(start_order(
+        &state,
+        &client,
+        OrderType::Auth,
+        request,
+        None,
+    )?))
+}
 
+#[axum::debug_handler]
+#[cfg(feature = "ssr")]
+async fn sign(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    client: RpClient,
+    RpJson(request): RpJson<AuthRequest>,
+) -> Result<Json<AuthResponse>, RpError> {
+    request
+        .validate(OrderType::Sign)
+        .map_err(RpError::InvalidParameters)?;
+    Ok(Json(start_order(
+        &state,
+        &client,
+        OrderType::Sign,
+        request,
+        None,
+    )?))
+}
+
+#[axum::debug_handler]
+#[cfg(feature = "ssr")]
+async fn payment(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    client: RpClient,
+    RpJson(request): RpJson<AuthRequest>,
+) -> Result<Json<AuthResponse>, RpError> {
+    request
+        .validate(OrderType::Payment)
+        .map_err(RpError::InvalidParameters)?;
+    Ok(Json(start_order(
+        &state,
+        &client,
+        OrderType::Payment,
+        request,
+        None,
+    )?))
+}
+
+#[axum::debug_handler]
+#[cfg(feature = "ssr")]
+async fn phone_auth(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    client: RpClient,
+    RpJson(request): RpJson<PhoneAuthRequest>,
+) -> Result<Json<PhoneResponse>, RpError> {
+    start_phone_order(&state, &client, OrderType::Auth, request).map(Json)
+}
+
+#[axum::debug_handler]
+#[cfg(feature = "ssr")]
+async fn phone_sign(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    client: RpClient,
+    RpJson(request): RpJson<PhoneAuthRequest>,
+) -> Result<Json<PhoneResponse>, RpError> {
+    start_phone_order(&state, &client, OrderType::Sign, request).map(Json)
+}
+
+#[cfg(feature = "ssr")]
+fn start_phone_order(
+    state: &AppState,
+    client: &RpClient,
+    order_type: OrderType,
+    request: PhoneAuthRequest,
+) -> Result<PhoneResponse, RpError> {
+    request
+        .validate(order_type)
+        .map_err(RpError::InvalidParameters)?;
+    let (request, call_initiator) = request.into_auth_request();
+    let response = start_order(state, client, order_type, request, Some(call_initiator))?;
+    Ok(PhoneResponse {
+        order_ref: response.order_ref,
+    })
+}
+
+#[cfg(feature = "ssr")]
+fn start_order(
+    state: &AppState,
+    client: &RpClient,
+    order_type: OrderType,
+    request: AuthRequest,
+    call_initiator: Option<CallInitiator>,
+) -> Result<AuthResponse, RpError> {
+    // Phone orders carry no endUserIp, so they are always grouped by the client ip.
+    let ip = match state.config.ip_source.unwrap_or_default() {
+        IpSource::EndUserIp if call_initiator.is_none() => request
+            .end_user_ip
+            .parse()
+            .map_err(|_| RpError::InvalidParameters("Invalid endUserIp".to_string()))?,
+        _ => client.ip,
+    };
     let uid = uuid::Uuid::new_v4();
-    let ip = insecure_ip.0;
-    {
-        let mut guard = state.orders.lock().unwrap();
+    let tokens = {
+        let mut guard = state.orders.lock()?;
 
-        guard.insert_empty(uid, ip);
-        drop(guard);
-    }
-    let mut server_signals = state.server_signals.clone();
-    let signal = server_signals
-        .get_signal::<ReadOnlySignal<i32>>("counter")
-        .unwrap();
-    signal.update(|x| {
-        *x += 1;
-    });
-    Json(AuthResponse {
+        let personal_number = request
+            .requirement
+            .as_ref()
+            .and_then(|r| r.personal_number.clone());
+        if let Some(personal_number) = personal_number {
+            if guard.cancel_pending_for(&personal_number) {
+                drop(guard);
+                notify_orders_changed(state);
+                return Err(RpError::AlreadyInProgress);
+            }
+        }
+        guard.insert_empty(
+            uid,
+            ip,
+            order_type,
+            request,
+            call_initiator,
+            client.rp.clone(),
+        )
+    };
+    notify_orders_changed(state);
+    Ok(AuthResponse {
         order_ref: uid.into(),
-        auto_start_token: "7c40b5c9-fa74-49cf-b98c-bfe651f9a7c6".into(),
-        qr_start_token: "67df3917-fa0d-44e5-b327-edcc928297f8".into(),
-        qr_start_secret: "d28db9a7-4cde-429e-a983-359be676944c".into(),
+        auto_start_token: tokens.auto_start_token.into(),
+        qr_start_token: tokens.qr_start_token.into(),
+        qr_start_secret: tokens.qr_start_secret.into(),
     })
 }
 
 #[cfg(feature = "ssr")]
 async fn collect(
     axum::extract::State(state): axum::extract::State<AppState>,
-    options: Json<CollectOptions>,
-) -> Json<CollectResponse> {
+    RpJson(options): RpJson<CollectOptions>,
+) -> Result<Json<CollectResponse>, RpError> {
     use bankid_mock::OrderEnum;
 
-    let guard = state.orders.lock().unwrap();
-    match guard.get(&options.order_ref) {
-        Some(OrderEnum::Completed(o)) => Json(CollectResponse {
-            order_ref: options.order_ref.clone().into(),
+    let mut guard = state.orders.lock()?;
+    let signature = guard
+        .signature(&options.order_ref, |order, user| {
+            bankid_mock::signature::sign_completion(&state.ca, order, user)
+        })
+        .unwrap_or_default();
+    let device = guard.device(&options.order_ref);
+    let response = match (guard.get(&options.order_ref), device) {
+        (Some(OrderEnum::Completed(o)), Some(device)) => CollectResponse {
+            order_ref: options.order_ref.into(),
             status: StatusEnum::Complete,
             hint_code: None,
             completion_data: Some(CompletionData {
                 user: o.clone(),
-                device: DeviceCompletionData {
-                    ip_adress: "192.168.1.1".to_string(),
-                },
-                bank_id_issue_date: "2023-01-01".to_string(),
-                signature: "".to_string(),
-                ocsp_response: "".to_string(),
+                device,
+                bank_id_issue_date: guard.bank_id_issue_date(&options.order_ref),
+                signature: signature.signature,
+                ocsp_response: signature.ocsp_response,
+                risk: guard.returned_risk(&options.order_ref),
+                step_up: guard.step_up(&options.order_ref),
             }),
-        }),
-        Some(OrderEnum::Expired) => Json(CollectResponse {
-            order_ref: options.order_ref.clone().into(),
+        },
+        (Some(OrderEnum::Failed(code)), _) => CollectResponse {
+            order_ref: options.order_ref.into(),
             status: StatusEnum::Failed,
-            hint_code: Some(HintCodes::Failed(FailedHintCodes::ExpiredTransaction)),
+            hint_code: Some(HintCodes::Failed(code.clone())),
             completion_data: None,
-        }),
-        Some(OrderEnum::Pending(o)) => Json(CollectResponse {
-            order_ref: options.order_ref.clone().into(),
+        },
+        (Some(OrderEnum::Pending(o)), _) => CollectResponse {
+            order_ref: options.order_ref.into(),
             status: StatusEnum::Pending,
             hint_code: Some(HintCodes::Pending(o.status.clone())),
             completion_data: None,
-        }),
-        None => Json(CollectResponse {
-            order_ref: options.order_ref.clone().into(),
-            status: StatusEnum::Pending,
-            hint_code: Some(HintCodes::Pending(PendingCode::Started)),
-            completion_data: None,
-        }),
+        },
+        // Unknown, or forgotten after the retention.
+        _ => return Err(RpError::NotFound),
+    };
+    Ok(Json(response))
+}
+
+#[cfg(feature = "ssr")]
+async fn cancel(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    RpJson(options): RpJson<CollectOptions>,
+) -> Result<Json<CancelResponse>, RpError> {
+    let cancelled = state
+        .orders
+        .lock()?
+        .fail(options.order_ref, FailedHintCodes::Cancelled);
+    if !cancelled {
+        return Err(RpError::InvalidParameters("No such order".to_string()));
+    }
+    notify_orders_changed(&state);
+    Ok(Json(CancelResponse {}))
+}
+
+/// Verifies the QR code of a digital ID card issued from the UI.
+#[cfg(feature = "ssr")]
+async fn verify(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    RpJson(request): RpJson<VerifyRequest>,
+) -> Result<Json<VerifyResponse>, RpError> {
+    use time::format_description::well_known::Rfc3339;
+
+    let digital_id = state
+        .orders
+        .lock()?
+        .get_digital_id(&request.qr_code)
+        .cloned()
+        .ok_or_else(|| RpError::InvalidParameters("Unknown qrCode".to_string()))?;
+    Ok(Json(VerifyResponse {
+        user: digital_id.user,
+        verification: Verification {
+            verification_id: uuid::Uuid::new_v4().into(),
+            verified_at: time::OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+        },
+        authentication: Authentication {
+            identified_at: digital_id
+                .identified_at
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+            order_ref: digital_id.order_ref.into(),
+        },
+    }))
+}
+
+/// Lets an RP check the animated QR codes it generates from `qrStartToken` and `qrStartSecret`.
+#[cfg(feature = "ssr")]
+async fn verify_qr(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Json(options): Json<VerifyQrOptions>,
+) -> Json<bankid_mock::qr::QrVerification> {
+    let guard = state.orders.lock().unwrap();
+    Json(guard.verify_qr_data(&options.qr_data))
+}
+
+/// The mock CA chain, for test environments that verify signatures and OCSP responses.
+#[cfg(feature = "ssr")]
+async fn ca_certificates(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> impl IntoResponse {
+    (
+        [(http::header::CONTENT_TYPE, "application/x-pem-file")],
+        state.ca.pem_chain(),
+    )
+}
+
+/// JSON API for test code to drive orders the way an operator would in the UI.
+#[cfg(feature = "ssr")]
+fn admin_routes() -> Router<AppState> {
+    use axum::routing::post;
+
+    Router::new()
+        .route("/orders", get(admin_orders))
+        .route("/orders/{id}", get(admin_order))
+        .route("/orders/{id}/complete", post(admin_complete))
+        .route("/orders/{id}/fail", post(admin_fail))
+        .route("/orders/{id}/status", post(admin_status))
+        .route("/orders/{id}/mrtd", post(admin_mrtd))
+        .fallback(|| async { RpError::NotFound })
+        .method_not_allowed_fallback(|| async { RpError::MethodNotAllowed })
+}
+
+#[cfg(feature = "ssr")]
+async fn admin_orders(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    axum::extract::Query(filter): axum::extract::Query<AdminOrdersFilter>,
+) -> Result<Json<Vec<AdminOrder>>, RpError> {
+    let alias_ip = filter.alias.as_ref().and_then(|alias| {
+        state
+            .config
+            .aliases
+            .iter()
+            .flatten()
+            .find(|a| &a.name == alias)
+            .map(|a| a.ip)
+    });
+    let orders = state
+        .orders
+        .lock()?
+        .get_all_with_state()
+        .into_iter()
+        .filter(|(order, _)| filter.ip.is_none_or(|ip| order.ip == ip))
+        .filter(|(order, _)| {
+            filter.alias.is_none()
+                || order.rp.is_some() && order.rp == filter.alias
+                || order.rp.is_none() && alias_ip == Some(order.ip)
+        })
+        .filter(|(_, data)| filter.status.is_none_or(|status| status == data.into()))
+        .map(AdminOrder::from)
+        .collect();
+    Ok(Json(orders))
+}
+
+#[cfg(feature = "ssr")]
+async fn admin_order(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Json<AdminOrder>, RpError> {
+    let order = state.orders.lock()?.get_with_state(&id);
+    order.map(|o| Json(o.into())).ok_or(RpError::NotFound)
+}
+
+#[cfg(feature = "ssr")]
+async fn admin_complete(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(id): Path<uuid::Uuid>,
+    RpJson(request): RpJson<AdminCompleteRequest>,
+) -> Result<Json<AdminOrder>, RpError> {
+    let (user, options) = match &request {
+        AdminCompleteRequest {
+            quick_user: Some(label),
+            ..
+        } => state
+            .config
+            .quick_users
+            .iter()
+            .flatten()
+            .find(|user| &user.label == label)
+            .map(|user| {
+                (
+                    UserCompletionData::new(user.ssn.clone(), user.name.clone()),
+                    user.completion_options(),
+                )
+            })
+            .ok_or_else(|| RpError::InvalidParameters("No such quick user".to_string()))?,
+        AdminCompleteRequest {
+            personal_number: Some(personal_number),
+            name: Some(name),
+            ..
+        } => (
+            UserCompletionData::new(personal_number.clone(), name.clone()),
+            CompletionOptions::default(),
+        ),
+        _ => {
+            return Err(RpError::InvalidParameters(
+                "Either quickUser or personalNumber and name is required".to_string(),
+            ))
+        }
+    };
+    let options = CompletionOptions {
+        risk: request.risk.unwrap_or(options.risk),
+        bank_id_type: request.bank_id_type.unwrap_or(options.bank_id_type),
+        device_ip: request.device_ip,
+        bank_id_issue_date: request.bank_id_issue_date.or(options.bank_id_issue_date),
+    };
+    update_pending_order(&state, id, |orders| {
+        orders.upgrade(id, user, options);
+    })
+}
+
+#[cfg(feature = "ssr")]
+async fn admin_fail(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(id): Path<uuid::Uuid>,
+    RpJson(request): RpJson<AdminFailRequest>,
+) -> Result<Json<AdminOrder>, RpError> {
+    update_pending_order(&state, id, |orders| {
+        orders.fail(id, request.hint_code);
+    })
+}
+
+#[cfg(feature = "ssr")]
+async fn admin_status(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(id): Path<uuid::Uuid>,
+    RpJson(request): RpJson<AdminStatusRequest>,
+) -> Result<Json<AdminOrder>, RpError> {
+    update_pending_order(&state, id, |orders| {
+        orders.set_pending_status(id, request.hint_code);
+    })
+}
+
+#[cfg(feature = "ssr")]
+async fn admin_mrtd(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(id): Path<uuid::Uuid>,
+    RpJson(request): RpJson<AdminMrtdRequest>,
+) -> Result<Json<AdminOrder>, RpError> {
+    let mut scanned = false;
+    let order = update_pending_order(&state, id, |orders| {
+        scanned = orders.scan_mrtd(&id, request.passed);
+    })?;
+    if !scanned {
+        return Err(RpError::InvalidParameters(
+            "Order is not waiting for a passport scan".to_string(),
+        ));
+    }
+    Ok(order)
+}
+
+/// Changes an order that is still pending, answering with the order as it is afterwards.
+#[cfg(feature = "ssr")]
+fn update_pending_order(
+    state: &AppState,
+    id: uuid::Uuid,
+    update: impl FnOnce(&mut OrderData),
+) -> Result<Json<AdminOrder>, RpError> {
+    use bankid_mock::OrderEnum;
+
+    let order = {
+        let mut guard = state.orders.lock()?;
+        match guard.get(&id) {
+            Some(OrderEnum::Pending(_)) => update(&mut guard),
+            Some(_) => {
+                return Err(RpError::InvalidParameters(
+                    "Order is no longer pending".to_string(),
+                ))
+            }
+            None => return Err(RpError::NotFound),
+        }
+        guard.get_with_state(&id).ok_or(RpError::NotFound)?
+    };
+    notify_orders_changed(state);
+    Ok(Json(order.into()))
+}
+
+/// Errors returned by the RP API, serialized as `{"errorCode", "details"}` like the real service.
+#[cfg(feature = "ssr")]
+#[derive(Debug, thiserror::Error)]
+pub enum RpError {
+    #[error("{0}")]
+    InvalidParameters(String),
+    #[error("Order already in progress for pno")]
+    AlreadyInProgress,
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("Not found")]
+    NotFound,
+    #[error("Method not allowed")]
+    MethodNotAllowed,
+    #[error("Unsupported media type")]
+    UnsupportedMediaType,
+    #[error("Internal error")]
+    InternalError,
+    #[error("Maintenance in progress")]
+    Maintenance,
+}
+
+#[cfg(feature = "ssr")]
+impl RpError {
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            RpError::InvalidParameters(_) => "invalidParameters",
+            RpError::AlreadyInProgress => "alreadyInProgress",
+            RpError::Unauthorized(_) => "unauthorized",
+            RpError::NotFound => "notFound",
+            RpError::MethodNotAllowed => "methodNotAllowed",
+            RpError::UnsupportedMediaType => "unsupportedMediaType",
+            RpError::InternalError => "internalError",
+            RpError::Maintenance => "maintenance",
+        }
+    }
+
+    pub fn status_code(&self) -> http::StatusCode {
+        match self {
+            RpError::InvalidParameters(_) | RpError::AlreadyInProgress => {
+                http::StatusCode::BAD_REQUEST
+            }
+            RpError::Unauthorized(_) => http::StatusCode::UNAUTHORIZED,
+            RpError::NotFound => http::StatusCode::NOT_FOUND,
+            RpError::MethodNotAllowed => http::StatusCode::METHOD_NOT_ALLOWED,
+            RpError::UnsupportedMediaType => http::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            RpError::InternalError => http::StatusCode::INTERNAL_SERVER_ERROR,
+            RpError::Maintenance => http::StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl IntoResponse for RpError {
+    fn into_response(self) -> AxumResponse {
+        let body = ErrorResponse {
+            error_code: self.error_code(),
+            details: self.to_string(),
+        };
+        (self.status_code(), Json(body)).into_response()
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<axum::extract::rejection::JsonRejection> for RpError {
+    fn from(rejection: axum::extract::rejection::JsonRejection) -> Self {
+        use axum::extract::rejection::JsonRejection;
+
+        match rejection {
+            JsonRejection::MissingJsonContentType(_) => RpError::UnsupportedMediaType,
+            other => RpError::InvalidParameters(other.body_text()),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl<T> From<std::sync::PoisonError<T>> for RpError {
+    fn from(_: std::sync::PoisonError<T>) -> Self {
+        RpError::InternalError
+    }
+}
+
+/// Who is calling the RP API, the client ip and the RP named by its client certificate.
+#[cfg(feature = "ssr")]
+struct RpClient {
+    ip: std::net::IpAddr,
+    rp: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+impl<S> axum::extract::FromRequestParts<S> for RpClient
+where
+    S: Send + Sync,
+    Option<std::sync::Arc<AcceptedClients>>: FromRef<S>,
+{
+    type Rejection = RpError;
+
+    async fn from_request_parts(
+        parts: &mut http::request::Parts,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        // Missing when the proxy in front of the mock does not set the `ip-source` header.
+        let ip = axum_client_ip::ClientIp::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| {
+                leptos::logging::warn!("Finding the client ip failed: {rejection}");
+                RpError::InternalError
+            })?
+            .0;
+        // Only certificates `authenticate` checked name an RP, anyone can present another.
+        let clients = Option::<std::sync::Arc<AcceptedClients>>::from_ref(state);
+        let rp = clients
+            .and(parts.extensions.get::<ClientCertificate>())
+            .and_then(ClientCertificate::rp_name);
+        Ok(RpClient { ip, rp })
+    }
+}
+
+/// `Json` extractor that rejects bad bodies with an [`RpError`].
+#[cfg(feature = "ssr")]
+#[derive(axum::extract::FromRequest)]
+#[from_request(via(axum::Json), rejection(RpError))]
+struct RpJson<T>(T);
+
+/// Runs the automations, then expires pending orders and forgets finished ones as
+/// `pending-timeout` and `retention` say.
+#[cfg(feature = "ssr")]
+async fn update_orders(state: AppState) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let changed = state
+            .orders
+            .lock()
+            .map(|mut orders| {
+                orders.automate(&state.config)
+                    + orders.remove_old(state.config.pending_timeout(), state.config.retention())
+            })
+            .unwrap_or_default();
+        if changed > 0 {
+            notify_orders_changed(&state);
+        }
+    }
+}
+
+/// Bumps the counter the UI listens on so open pages refetch their orders.
+#[cfg(feature = "ssr")]
+fn notify_orders_changed(state: &AppState) {
+    use leptos_ws::ReadOnlySignal;
+
+    let mut server_signals = state.server_signals.clone();
+    if let Some(signal) = server_signals.get_signal::<ReadOnlySignal<i32>>("counter") {
+        signal.update(|x| {
+            *x += 1;
+        });
     }
 }
 use axum::extract::FromRef;
@@ -244,6 +851,8 @@ pub struct AppState {
     pub routes: Option<Vec<AxumRouteListing>>,
     pub orders: Orders,
     pub config: ConfigState,
+    pub ca: std::sync::Arc<MockCa>,
+    pub clients: Option<std::sync::Arc<AcceptedClients>>,
 }
 
 #[derive(Serialize)]
@@ -256,6 +865,11 @@ pub struct AuthResponse {
 }
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
+pub struct PhoneResponse {
+    order_ref: String,
+}
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 #[serde(untagged)]
 pub enum HintCodes {
     Pending(PendingCode),
@@ -274,30 +888,173 @@ pub struct CollectResponse {
 pub struct CompletionData {
     user: UserCompletionData,
     device: DeviceCompletionData,
-    bank_id_issue_date: String,
+    #[serde(
+        with = "bankid_mock::issue_date::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    bank_id_issue_date: Option<time::Date>,
     signature: String,
     ocsp_response: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    risk: Option<RiskLevel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    step_up: Option<bankid_mock::StepUp>,
 }
 
+#[derive(Serialize)]
+pub struct CancelResponse {}
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyResponse {
+    user: UserCompletionData,
+    verification: Verification,
+    authentication: Authentication,
+}
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Verification {
+    verification_id: String,
+    verified_at: String,
+}
+/// How the user identified themselves when opening the digital ID card.
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
-pub enum FailedHintCodes {
-    ExpiredTransaction,
+pub struct Authentication {
+    identified_at: String,
+    order_ref: String,
 }
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
+pub struct ErrorResponse {
+    error_code: &'static str,
+    details: String,
+}
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub enum StatusEnum {
     Pending,
     Complete,
     Failed,
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VerifyQrOptions {
+    qr_data: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VerifyRequest {
+    qr_code: String,
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct CollectOptions {
     order_ref: uuid::Uuid,
 }
 
+/// An order as the admin API lists it.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminOrder {
+    order_ref: String,
+    order_type: OrderType,
+    order_time: String,
+    ip: std::net::IpAddr,
+    rp: Option<String>,
+    status: StatusEnum,
+    hint_code: Option<HintCodes>,
+    /// The personal number the RP required, if any.
+    personal_number: Option<String>,
+    user_visible_data: Option<String>,
+    user: Option<UserCompletionData>,
+}
+
+#[cfg(feature = "ssr")]
+impl From<(bankid_mock::OrderSummary, bankid_mock::OrderEnum)> for AdminOrder {
+    fn from((order, data): (bankid_mock::OrderSummary, bankid_mock::OrderEnum)) -> Self {
+        use bankid_mock::OrderEnum;
+
+        let (hint_code, user) = match &data {
+            OrderEnum::Pending(pending) => (Some(HintCodes::Pending(pending.status.clone())), None),
+            OrderEnum::Failed(code) => (Some(HintCodes::Failed(code.clone())), None),
+            OrderEnum::Completed(user) => (None, Some(user.clone())),
+        };
+        AdminOrder {
+            order_ref: order.id.into(),
+            order_type: order.order_type,
+            order_time: order
+                .order_time
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_default(),
+            ip: order.ip,
+            rp: order.rp,
+            status: (&data).into(),
+            hint_code,
+            personal_number: order.request.requirement.and_then(|r| r.personal_number),
+            user_visible_data: order.user_visible_data,
+            user,
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<&bankid_mock::OrderEnum> for StatusEnum {
+    fn from(data: &bankid_mock::OrderEnum) -> Self {
+        match data {
+            bankid_mock::OrderEnum::Pending(_) => StatusEnum::Pending,
+            bankid_mock::OrderEnum::Completed(_) => StatusEnum::Complete,
+            bankid_mock::OrderEnum::Failed(_) => StatusEnum::Failed,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AdminOrdersFilter {
+    alias: Option<String>,
+    ip: Option<std::net::IpAddr>,
+    status: Option<StatusEnum>,
+}
+
+/// Completes as a configured quick user, or as the given person.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AdminCompleteRequest {
+    quick_user: Option<String>,
+    personal_number: Option<String>,
+    name: Option<String>,
+    /// The quick user's risk, or low, when left out.
+    risk: Option<RiskLevel>,
+    /// The quick user's kind of BankID, or mobile, when left out.
+    bank_id_type: Option<BankIdType>,
+    /// The endUserIp of the order, or a random ip for phone orders, when left out.
+    device_ip: Option<std::net::IpAddr>,
+    /// The quick user's issue date, or a random one, when left out.
+    #[serde(default, with = "bankid_mock::issue_date::option")]
+    bank_id_issue_date: Option<time::Date>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AdminFailRequest {
+    hint_code: FailedHintCodes,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AdminStatusRequest {
+    hint_code: PendingCode,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AdminMrtdRequest {
+    passed: bool,
+}
+
 #[cfg(not(feature = "ssr"))]
 pub fn main() {
     // no client-side main function
@@ -0,0 +1,188 @@
+use hmac::{Hmac, Mac};
+use qrcode::render::svg;
+use qrcode::QrCode;
+use serde::Serialize;
+use sha2::Sha256;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{OrderData, OrderEnum};
+
+/// How far off the time in a QR code may be, to allow for latency between RP and mock.
+const TIME_TOLERANCE_SECONDS: i64 = 2;
+
+/// `bankid.<qrStartToken>.<qrTime>.<qrAuthCode>` as described in the BankID documentation.
+pub fn qr_data(qr_start_token: &Uuid, qr_start_secret: &Uuid, qr_time: i64) -> String {
+    format!(
+        "bankid.{}.{}.{}",
+        qr_start_token,
+        qr_time,
+        qr_auth_code(qr_start_secret, qr_time)
+    )
+}
+
+/// HMAC-SHA256 of the qrTime keyed with the qrStartSecret, hex encoded.
+pub fn qr_auth_code(qr_start_secret: &Uuid, qr_time: i64) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(qr_start_secret.to_string().as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(qr_time.to_string().as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn render_svg(data: &str) -> String {
+    QrCode::new(data.as_bytes())
+        .map(|code| {
+            code.render::<svg::Color>()
+                .min_dimensions(160, 160)
+                .quiet_zone(true)
+                .build()
+        })
+        .unwrap_or_default()
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QrVerification {
+    pub valid: bool,
+    pub order_ref: Option<Uuid>,
+    pub hmac_valid: bool,
+    pub time_valid: bool,
+    pub qr_time: Option<i64>,
+    pub expected_qr_time: Option<i64>,
+    pub details: String,
+}
+
+impl QrVerification {
+    fn invalid(details: &str) -> Self {
+        QrVerification {
+            valid: false,
+            order_ref: None,
+            hmac_valid: false,
+            time_valid: false,
+            qr_time: None,
+            expected_qr_time: None,
+            details: details.to_string(),
+        }
+    }
+}
+
+impl OrderData {
+    /// The QR data the app would currently scan for a pending order.
+    pub fn current_qr_data(&self, id: &Uuid) -> Option<String> {
+        let order = self.data.get(id)?;
+        if !matches!(order.data, OrderEnum::Pending(_)) || order.call_initiator.is_some() {
+            return None;
+        }
+        let qr_time = (OffsetDateTime::now_utc() - order.order_time).whole_seconds();
+        Some(qr_data(
+            &order.tokens.qr_start_token,
+            &order.tokens.qr_start_secret,
+            qr_time,
+        ))
+    }
+
+    /// Checks a QR string generated by an RP against the order it was generated for.
+    pub fn verify_qr_data(&self, value: &str) -> QrVerification {
+        let parts: Vec<_> = value.split('.').collect();
+        let [prefix, token, qr_time, auth_code] = parts.as_slice() else {
+            return QrVerification::invalid("Expected bankid.<token>.<time>.<code>");
+        };
+        if *prefix != "bankid" {
+            return QrVerification::invalid("Missing bankid prefix");
+        }
+        let Ok(token) = token.parse::<Uuid>() else {
+            return QrVerification::invalid("qrStartToken is not a uuid");
+        };
+        let Ok(qr_time) = qr_time.parse::<i64>() else {
+            return QrVerification::invalid("qrTime is not a number");
+        };
+        let Some((id, order)) = self
+            .data
+            .iter()
+            .find(|(_, o)| o.tokens.qr_start_token == token)
+        else {
+            return QrVerification::invalid("No order with that qrStartToken");
+        };
+
+        let expected_qr_time = (OffsetDateTime::now_utc() - order.order_time).whole_seconds();
+        let hmac_valid = qr_auth_code(&order.tokens.qr_start_secret, qr_time) == *auth_code;
+        let time_valid = (expected_qr_time - qr_time).abs() <= TIME_TOLERANCE_SECONDS;
+        let details = match (hmac_valid, time_valid) {
+            (true, true) => "Ok",
+            (false, _) => "qrAuthCode does not match HMAC-SHA256(qrStartSecret, qrTime)",
+            (true, false) => "qrTime is not the seconds since the order was started",
+        };
+        QrVerification {
+            valid: hmac_valid && time_valid,
+            order_ref: Some(*id),
+            hmac_valid,
+            time_valid,
+            qr_time: Some(qr_time),
+            expected_qr_time: Some(expected_qr_time),
+            details: details.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::AuthRequest;
+    use crate::OrderType;
+
+    /// The example from the BankID documentation.
+    const QR_START_TOKEN: &str = "67df3917-fa0d-44e5-b327-edcc928297f8";
+    const QR_START_SECRET: &str = "d28db9a7-4cde-429e-a983-359be676944c";
+
+    #[test]
+    fn qr_auth_code_matches_the_documentation() {
+        let secret = QR_START_SECRET.parse().unwrap();
+        assert_eq!(
+            qr_auth_code(&secret, 0),
+            "dc69358e712458a66a7525beef148ae8526b1c71610eff2c16cdffb4cdac9bf8"
+        );
+        assert_eq!(
+            qr_auth_code(&secret, 1),
+            "949d559bf23403952a94d103e67743126381eda00f0b3cbddbf7c96b1adcbce2"
+        );
+        assert_eq!(
+            qr_data(&QR_START_TOKEN.parse().unwrap(), &secret, 2),
+            format!(
+                "bankid.{QR_START_TOKEN}.2.{}",
+                "a9e5ec59cb4eee4ef4117150abc58fad7a85439a6a96ccbecc3668b41795b3f3"
+            )
+        );
+    }
+
+    #[test]
+    fn verify_qr_data_checks_the_code_and_time() {
+        let mut orders = OrderData::new();
+        let id = Uuid::new_v4();
+        let tokens = orders.insert_empty(
+            id,
+            "127.0.0.1".parse().unwrap(),
+            OrderType::Auth,
+            AuthRequest::default(),
+            None,
+            None,
+        );
+        let current = orders.current_qr_data(&id).unwrap();
+        let verification = orders.verify_qr_data(&current);
+        assert!(verification.valid);
+        assert_eq!(verification.order_ref, Some(id));
+
+        let wrong_secret = qr_data(&tokens.qr_start_token, &Uuid::new_v4(), 0);
+        let verification = orders.verify_qr_data(&wrong_secret);
+        assert!(!verification.hmac_valid && verification.time_valid);
+
+        let late = qr_data(&tokens.qr_start_token, &tokens.qr_start_secret, 60);
+        let verification = orders.verify_qr_data(&late);
+        assert!(verification.hmac_valid && !verification.time_valid);
+
+        assert!(!orders.verify_qr_data("bankid.not-a-token.0.00").valid);
+    }
+}
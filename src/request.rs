@@ -0,0 +1,575 @@
+use std::net::IpAddr;
+
+use base64::Engine;
+use serde::Deserialize;
+use serde::Serialize;
+use strum::{Display, IntoEnumIterator};
+
+use crate::{BankIdType, OrderType};
+
+/// The body of an auth request, sign takes the same body but requires `userVisibleData`
+/// and payment requires `userVisibleTransaction`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthRequest {
+    pub end_user_ip: String,
+    pub requirement: Option<Requirement>,
+    pub user_visible_data: Option<String>,
+    pub user_non_visible_data: Option<String>,
+    pub user_visible_data_format: Option<UserVisibleDataFormat>,
+    pub return_url: Option<String>,
+    pub return_risk: Option<bool>,
+    pub app: Option<AppDeviceParameters>,
+    pub web: Option<WebDeviceParameters>,
+    pub user_visible_transaction: Option<UserVisibleTransaction>,
+    pub risk_flags: Option<Vec<RiskFlag>>,
+}
+
+/// The body of a phone auth or sign request, where the personal number is mandatory.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PhoneAuthRequest {
+    pub personal_number: String,
+    pub call_initiator: CallInitiator,
+    pub requirement: Option<Requirement>,
+    pub user_visible_data: Option<String>,
+    pub user_non_visible_data: Option<String>,
+    pub user_visible_data_format: Option<UserVisibleDataFormat>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Display)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum CallInitiator {
+    User,
+    #[serde(rename = "RP")]
+    #[strum(serialize = "RP")]
+    Rp,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Requirement {
+    pub pin_code: Option<bool>,
+    pub mrtd: Option<bool>,
+    pub card_reader: Option<CardReader>,
+    pub certificate_policies: Option<Vec<String>>,
+    pub personal_number: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Display)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum CardReader {
+    Class1,
+    Class2,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Display)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum UserVisibleDataFormat {
+    SimpleMarkdownV1,
+}
+
+/// The transaction the user approves in a payment order.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UserVisibleTransaction {
+    pub transaction_type: TransactionType,
+    pub recipient: Recipient,
+    pub money: Option<Money>,
+    pub risk_warning: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Display)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum TransactionType {
+    Card,
+    Npa,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Recipient {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Money {
+    pub amount: String,
+    pub currency: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Display)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum RiskFlag {
+    NewCard,
+    NewCustomer,
+    NewRecipient,
+    HighRiskRecipient,
+    LargeAmount,
+    ForeignCurrency,
+    CryptoCurrencyPurchase,
+    MoneyTransfer,
+    OverseasTransaction,
+    RecurringPayment,
+    SuspiciousPaymentPattern,
+    Other,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppDeviceParameters {
+    pub app_identifier: String,
+    #[serde(rename = "deviceOS")]
+    pub device_os: String,
+    pub device_model_name: String,
+    pub device_identifier: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WebDeviceParameters {
+    pub referring_domain: String,
+    pub user_agent: String,
+    pub device_identifier: String,
+}
+
+impl AuthRequest {
+    /// Checks the request the way the real service does, the error is used as `details`.
+    pub fn validate(&self, order_type: OrderType) -> Result<(), String> {
+        if self.end_user_ip.parse::<IpAddr>().is_err() {
+            return Err("Invalid endUserIp".to_string());
+        }
+        if let Some(requirement) = &self.requirement {
+            requirement.validate()?;
+        }
+
+        validate_user_data(
+            order_type,
+            self.user_visible_data.as_deref(),
+            self.user_non_visible_data.as_deref(),
+            self.user_visible_data_format,
+        )?;
+
+        match (&self.user_visible_transaction, order_type) {
+            (Some(transaction), OrderType::Payment) => transaction.validate()?,
+            (None, OrderType::Payment) => {
+                return Err("Missing userVisibleTransaction".to_string());
+            }
+            (Some(_), _) => {
+                return Err("userVisibleTransaction is only allowed for payment".to_string());
+            }
+            (None, _) => {}
+        }
+        if self.risk_flags.is_some() && order_type != OrderType::Payment {
+            return Err("riskFlags is only allowed for payment".to_string());
+        }
+
+        if let Some(return_url) = &self.return_url {
+            let valid = return_url
+                .parse::<http::Uri>()
+                .is_ok_and(|uri| uri.scheme().is_some());
+            if !valid {
+                return Err("Invalid returnUrl".to_string());
+            }
+        }
+
+        match (&self.app, &self.web) {
+            (Some(_), Some(_)) => return Err("Only one of app and web may be set".to_string()),
+            (Some(app), None) => {
+                if app.app_identifier.is_empty()
+                    || app.device_os.is_empty()
+                    || app.device_identifier.is_empty()
+                {
+                    return Err("Invalid app".to_string());
+                }
+            }
+            (None, Some(web)) => {
+                if web.referring_domain.is_empty() || web.device_identifier.is_empty() {
+                    return Err("Invalid web".to_string());
+                }
+            }
+            (None, None) => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl PhoneAuthRequest {
+    /// Checks the request the way the real service does, the error is used as `details`.
+    pub fn validate(&self, order_type: OrderType) -> Result<(), String> {
+        if !is_personal_number(&self.personal_number) {
+            return Err("Invalid personalNumber".to_string());
+        }
+        if let Some(requirement) = &self.requirement {
+            if requirement.personal_number.is_some() {
+                return Err("personalNumber is not allowed in requirement".to_string());
+            }
+            requirement.validate()?;
+        }
+        validate_user_data(
+            order_type,
+            self.user_visible_data.as_deref(),
+            self.user_non_visible_data.as_deref(),
+            self.user_visible_data_format,
+        )
+    }
+
+    /// Stores the phone request like any other, with the personal number as a requirement.
+    pub fn into_auth_request(self) -> (AuthRequest, CallInitiator) {
+        let requirement = Requirement {
+            personal_number: Some(self.personal_number),
+            ..self.requirement.unwrap_or_default()
+        };
+        let request = AuthRequest {
+            requirement: Some(requirement),
+            user_visible_data: self.user_visible_data,
+            user_non_visible_data: self.user_non_visible_data,
+            user_visible_data_format: self.user_visible_data_format,
+            ..AuthRequest::default()
+        };
+        (request, self.call_initiator)
+    }
+}
+
+impl UserVisibleTransaction {
+    fn validate(&self) -> Result<(), String> {
+        if self.recipient.name.trim().is_empty() {
+            return Err("Invalid recipient name".to_string());
+        }
+        if let Some(money) = &self.money {
+            money.validate()?;
+        }
+        Ok(())
+    }
+}
+
+impl Money {
+    /// An amount like `499,00` (or `499.00`) in an ISO 4217 currency like `SEK`.
+    fn validate(&self) -> Result<(), String> {
+        let (whole, fraction) = match self.amount.split_once([',', '.']) {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (self.amount.as_str(), None),
+        };
+        let is_digits = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+        let valid_amount =
+            is_digits(whole) && fraction.is_none_or(|f| f.len() <= 2 && is_digits(f));
+        if !valid_amount {
+            return Err("Invalid money amount".to_string());
+        }
+        if self.currency.len() != 3 || !self.currency.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err("Invalid money currency".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Requirement {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(personal_number) = &self.personal_number {
+            if !is_personal_number(personal_number) {
+                return Err("Invalid personalNumber".to_string());
+            }
+        }
+        if let Some(policies) = &self.certificate_policies {
+            // A single trailing wildcard is allowed from the fifth part on, like `1.2.752.78.*`.
+            let valid_oid = |policy: &String| {
+                let parts: Vec<_> = policy.split('.').collect();
+                parts.iter().enumerate().all(|(i, part)| {
+                    let wildcard = *part == "*" && i == parts.len() - 1 && i >= 4;
+                    wildcard || !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())
+                })
+            };
+            if policies.is_empty() || !policies.iter().all(valid_oid) {
+                return Err("Invalid certificatePolicies".to_string());
+            }
+        }
+        if self.card_reader.is_some() && !BankIdType::iter().any(|t| t.is_card() && self.allows(t))
+        {
+            return Err("cardReader requires a smart card certificate policy".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether a user with the kind of BankID may complete the order, any kind unless the
+    /// certificate policies or a card reader say otherwise.
+    pub fn allows(&self, bank_id_type: BankIdType) -> bool {
+        let policy = bank_id_type.certificate_policy();
+        let allowed_policy = self.certificate_policies.as_ref().is_none_or(|policies| {
+            policies
+                .iter()
+                .any(|allowed| match allowed.strip_suffix('*') {
+                    Some(prefix) => policy.starts_with(prefix),
+                    None => allowed == policy,
+                })
+        });
+        allowed_policy && (self.card_reader.is_none() || bank_id_type.is_card())
+    }
+
+    /// Every kind of BankID the requirement [`allows`](Requirement::allows), mobile first.
+    pub fn allowed_bank_id_types(&self) -> Vec<BankIdType> {
+        BankIdType::iter()
+            .filter(|kind| self.allows(*kind))
+            .collect()
+    }
+}
+
+/// The size and encoding rules for the data shown to, and hidden from, the user.
+fn validate_user_data(
+    order_type: OrderType,
+    user_visible_data: Option<&str>,
+    user_non_visible_data: Option<&str>,
+    user_visible_data_format: Option<UserVisibleDataFormat>,
+) -> Result<(), String> {
+    let (visible_limit, non_visible_limit) = match order_type {
+        OrderType::Auth | OrderType::Payment => (1_500, 1_500),
+        OrderType::Sign => (40_000, 200_000),
+    };
+    match user_visible_data {
+        Some(data) => {
+            let text = decode_base64("userVisibleData", data, visible_limit)?;
+            if String::from_utf8(text).is_err() {
+                return Err("userVisibleData must be UTF-8 encoded text".to_string());
+            }
+        }
+        None if order_type == OrderType::Sign => {
+            return Err("Missing userVisibleData".to_string());
+        }
+        None => {}
+    }
+    if let Some(data) = user_non_visible_data {
+        decode_base64("userNonVisibleData", data, non_visible_limit)?;
+    }
+    if user_visible_data_format.is_some() && user_visible_data.is_none() {
+        return Err("userVisibleDataFormat requires userVisibleData".to_string());
+    }
+    Ok(())
+}
+
+/// Whether the url is an absolute http or https url, parsed like `returnUrl`.
+pub fn is_web_url(url: &str) -> bool {
+    url.parse::<http::Uri>().is_ok_and(|uri| {
+        matches!(uri.scheme_str(), Some("http" | "https")) && uri.authority().is_some()
+    })
+}
+
+fn is_personal_number(value: &str) -> bool {
+    value.len() == 12 && value.chars().all(|c| c.is_ascii_digit())
+}
+
+fn decode_base64(field: &str, value: &str, max_length: usize) -> Result<Vec<u8>, String> {
+    if value.is_empty() || value.len() > max_length {
+        return Err(format!("{field} must be 1-{max_length} characters"));
+    }
+    base64::engine::general_purpose::STANDARD
+        .decode(value)
+        .map_err(|_| format!("{field} is not valid base64"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn web_urls_are_absolute_http_or_https() {
+        assert!(is_web_url("https://example.com/done?order=1"));
+        assert!(is_web_url("http://localhost:3000"));
+        assert!(!is_web_url("javascript:alert(1)"));
+        assert!(!is_web_url("javascript://example.com/%0aalert(1)"));
+        assert!(!is_web_url("/relative/path"));
+        assert!(!is_web_url("myapp://callback"));
+        assert!(!is_web_url(""));
+    }
+
+    fn requirement(policies: &[&str], card_reader: Option<CardReader>) -> Requirement {
+        Requirement {
+            certificate_policies: Some(policies.iter().map(|p| p.to_string()).collect()),
+            card_reader,
+            ..Default::default()
+        }
+    }
+
+    fn auth(requirement: Requirement) -> Result<(), String> {
+        AuthRequest {
+            end_user_ip: "127.0.0.1".to_string(),
+            requirement: Some(requirement),
+            ..Default::default()
+        }
+        .validate(OrderType::Auth)
+    }
+
+    #[test]
+    fn certificate_policies_are_oids_with_an_optional_trailing_wildcard() {
+        assert_eq!(auth(requirement(&["1.2.752.78.1.5"], None)), Ok(()));
+        assert_eq!(auth(requirement(&["1.2.752.78.*"], None)), Ok(()));
+        for invalid in [
+            &[][..],
+            &["1.2.*"],
+            &["1.2.752.*.1"],
+            &["1.2.752.78.1.x"],
+            &[""],
+        ] {
+            assert_eq!(
+                auth(requirement(invalid, None)),
+                Err("Invalid certificatePolicies".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn card_reader_requires_a_card_policy() {
+        assert_eq!(
+            auth(Requirement {
+                card_reader: Some(CardReader::Class1),
+                ..Default::default()
+            }),
+            Ok(())
+        );
+        assert_eq!(
+            auth(requirement(&["1.2.752.78.1.2"], Some(CardReader::Class2))),
+            Ok(())
+        );
+        assert_eq!(
+            auth(requirement(&["1.2.752.78.1.5"], Some(CardReader::Class1))),
+            Err("cardReader requires a smart card certificate policy".to_string())
+        );
+    }
+
+    #[test]
+    fn policies_allow_the_kinds_of_bank_id_they_name() {
+        assert_eq!(
+            Requirement::default().allowed_bank_id_types(),
+            BankIdType::iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            requirement(&["1.2.3.4.25"], None).allowed_bank_id_types(),
+            vec![BankIdType::TestMobile]
+        );
+        assert_eq!(
+            requirement(&["1.2.752.78.*"], None).allowed_bank_id_types(),
+            vec![BankIdType::Mobile, BankIdType::File, BankIdType::Card]
+        );
+        assert_eq!(
+            requirement(&["1.2.752.78.1.5", "1.2.3.4.10"], None).allowed_bank_id_types(),
+            vec![BankIdType::Mobile, BankIdType::TestCard]
+        );
+        let card_reader = Requirement {
+            card_reader: Some(CardReader::Class1),
+            ..Default::default()
+        };
+        assert_eq!(
+            card_reader.allowed_bank_id_types(),
+            vec![BankIdType::Card, BankIdType::TestCard]
+        );
+    }
+
+    fn validate(order_type: OrderType, json: serde_json::Value) -> Result<(), String> {
+        serde_json::from_value::<AuthRequest>(json)
+            .unwrap()
+            .validate(order_type)
+    }
+
+    #[test]
+    fn auth_requests_need_a_valid_end_user_ip() {
+        assert_eq!(
+            validate(
+                OrderType::Auth,
+                serde_json::json!({"endUserIp": "127.0.0.1"})
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            validate(
+                OrderType::Auth,
+                serde_json::json!({"endUserIp": "localhost"})
+            ),
+            Err("Invalid endUserIp".to_string())
+        );
+    }
+
+    #[test]
+    fn sign_requests_need_base64_user_visible_data() {
+        let request = |data: Option<&str>| {
+            validate(
+                OrderType::Sign,
+                serde_json::json!({"endUserIp": "::1", "userVisibleData": data}),
+            )
+        };
+        assert_eq!(request(Some("SGVqIQ==")), Ok(()));
+        assert_eq!(request(None), Err("Missing userVisibleData".to_string()));
+        assert_eq!(
+            request(Some("not base64!")),
+            Err("userVisibleData is not valid base64".to_string())
+        );
+        assert_eq!(
+            request(Some("/w==")),
+            Err("userVisibleData must be UTF-8 encoded text".to_string())
+        );
+        let too_long = "A".repeat(1_504);
+        assert_eq!(
+            validate(
+                OrderType::Auth,
+                serde_json::json!({"endUserIp": "::1", "userVisibleData": too_long}),
+            ),
+            Err("userVisibleData must be 1-1500 characters".to_string())
+        );
+    }
+
+    #[test]
+    fn requirement_personal_numbers_have_twelve_digits() {
+        let request = |personal_number: &str| {
+            validate(
+                OrderType::Auth,
+                serde_json::json!({
+                    "endUserIp": "127.0.0.1",
+                    "requirement": {"personalNumber": personal_number},
+                }),
+            )
+        };
+        assert_eq!(request("201604064188"), Ok(()));
+        assert_eq!(
+            request("1604064188"),
+            Err("Invalid personalNumber".to_string())
+        );
+    }
+
+    #[test]
+    fn app_and_web_are_exclusive_and_complete() {
+        let app = serde_json::json!({
+            "appIdentifier": "se.example.app",
+            "deviceOS": "iOS 17",
+            "deviceModelName": "iPhone",
+            "deviceIdentifier": "abc",
+        });
+        let web = serde_json::json!({
+            "referringDomain": "example.com",
+            "userAgent": "Firefox",
+            "deviceIdentifier": "abc",
+        });
+        let request = |json| validate(OrderType::Auth, json);
+        assert_eq!(
+            request(serde_json::json!({"endUserIp": "127.0.0.1", "app": app})),
+            Ok(())
+        );
+        assert_eq!(
+            request(serde_json::json!({"endUserIp": "127.0.0.1", "web": web})),
+            Ok(())
+        );
+        assert_eq!(
+            request(serde_json::json!({"endUserIp": "127.0.0.1", "app": app, "web": web})),
+            Err("Only one of app and web may be set".to_string())
+        );
+        let mut no_domain = web.clone();
+        no_domain["referringDomain"] = "".into();
+        assert_eq!(
+            request(serde_json::json!({"endUserIp": "127.0.0.1", "web": no_domain})),
+            Err("Invalid web".to_string())
+        );
+    }
+}
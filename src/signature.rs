@@ -0,0 +1,222 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+use crate::ca::MockCa;
+use crate::{CompletionSignature, OrderSummary, OrderType, UserCompletionData};
+
+const DSIG_NS: &str = "http://www.w3.org/2000/09/xmldsig#";
+const BANKID_NS: &str = "http://www.bankid.com/signature/v1.0.0/types";
+const C14N: &str = "http://www.w3.org/TR/2001/REC-xml-c14n-20010315";
+
+/// The `signature` and `ocspResponse` of a completed order, both base64 encoded.
+pub fn sign_completion(
+    ca: &MockCa,
+    order: &OrderSummary,
+    user: &UserCompletionData,
+) -> CompletionSignature {
+    let document = signature_xml(ca, order, user);
+    let ocsp_response = ca.ocsp_response(&ca.user_certificate(user), document.as_bytes());
+    CompletionSignature {
+        signature: STANDARD.encode(document),
+        ocsp_response: STANDARD.encode(ocsp_response),
+    }
+}
+
+/// The XML-DSig document the user signs.
+///
+/// Laid out like the real one, the signed data in `bidSignedData` and the certificate chain in
+/// `bidKeyInfo`, both referenced from `SignedInfo`. The elements are written in canonical form,
+/// so the digests can be checked with any C14N implementation.
+fn signature_xml(ca: &MockCa, order: &OrderSummary, user: &UserCompletionData) -> String {
+    let certificate = ca.user_certificate(user);
+    let request = &order.request;
+
+    let visible_data = request
+        .user_visible_data
+        .as_ref()
+        .map(|data| {
+            format!(r#"<usrVisibleData charset="UTF-8" visible="wysiwys">{data}</usrVisibleData>"#)
+        })
+        .unwrap_or_default();
+    let non_visible_data = request
+        .user_non_visible_data
+        .as_ref()
+        .map(|data| format!("<usrNonVisibleData>{data}</usrNonVisibleData>"))
+        .unwrap_or_default();
+    let function = match order.order_type {
+        OrderType::Auth => "Identification",
+        OrderType::Sign => "Signing",
+        OrderType::Payment => "Payment",
+    };
+    let nonce = STANDARD.encode(rand::random::<[u8; 32]>());
+    let signed_data = format!(
+        concat!(
+            r#"<bankIdSignedData xmlns="{ns}" Id="bidSignedData">"#,
+            "{visible_data}{non_visible_data}",
+            "<srvInfo><name>{name}</name><nonce>{nonce}</nonce></srvInfo>",
+            "<clientInfo><funcId>{function}</funcId><host><hostName></hostName>",
+            "<hostIpAddress>{ip}</hostIpAddress></host></clientInfo>",
+            "</bankIdSignedData>",
+        ),
+        ns = BANKID_NS,
+        visible_data = visible_data,
+        non_visible_data = non_visible_data,
+        name = STANDARD.encode("Mock BankID RP"),
+        nonce = nonce,
+        function = function,
+        ip = escape(&request.end_user_ip),
+    );
+
+    let chain = [
+        certificate.certificate.der().as_ref(),
+        ca.issuing().as_ref(),
+        ca.root().as_ref(),
+    ]
+    .map(|der| {
+        format!(
+            "<X509Certificate>{}</X509Certificate>",
+            STANDARD.encode(der)
+        )
+    })
+    .concat();
+    let key_info = format!(
+        r#"<KeyInfo xmlns="{DSIG_NS}" Id="bidKeyInfo"><X509Data>{chain}</X509Data></KeyInfo>"#
+    );
+
+    let signed_info = format!(
+        concat!(
+            r#"<SignedInfo xmlns="{ns}">"#,
+            r#"<CanonicalizationMethod Algorithm="{c14n}"></CanonicalizationMethod>"#,
+            r#"<SignatureMethod Algorithm="http://www.w3.org/2001/04/xmldsig-more#ecdsa-sha256">"#,
+            "</SignatureMethod>",
+            "{signed_data_reference}{key_info_reference}",
+            "</SignedInfo>",
+        ),
+        ns = DSIG_NS,
+        c14n = C14N,
+        signed_data_reference = reference(Some(BANKID_NS), "bidSignedData", &signed_data),
+        key_info_reference = reference(None, "bidKeyInfo", &key_info),
+    );
+    let signature_value = STANDARD.encode(certificate.sign(signed_info.as_bytes()));
+
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>"#,
+            r#"<Signature xmlns="{DSIG_NS}">"#,
+            "{signed_info}<SignatureValue>{signature_value}</SignatureValue>{key_info}",
+            "<Object>{signed_data}</Object>",
+            "</Signature>",
+        ),
+        DSIG_NS = DSIG_NS,
+        signed_info = signed_info,
+        signature_value = signature_value,
+        key_info = key_info,
+        signed_data = signed_data,
+    )
+}
+
+fn reference(reference_type: Option<&str>, id: &str, element: &str) -> String {
+    let reference_type = reference_type
+        .map(|t| format!(r#" Type="{t}""#))
+        .unwrap_or_default();
+    let digest = STANDARD.encode(Sha256::digest(element.as_bytes()));
+    format!(
+        concat!(
+            r##"<Reference{reference_type} URI="#{id}">"##,
+            r#"<Transforms><Transform Algorithm="{c14n}"></Transform></Transforms>"#,
+            r#"<DigestMethod Algorithm="http://www.w3.org/2001/04/xmlenc#sha256"></DigestMethod>"#,
+            "<DigestValue>{digest}</DigestValue>",
+            "</Reference>",
+        ),
+        reference_type = reference_type,
+        id = id,
+        c14n = C14N,
+        digest = digest,
+    )
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_FIXED};
+    use time::OffsetDateTime;
+    use uuid::Uuid;
+    use x509_parser::prelude::{FromDer, X509Certificate};
+
+    use super::*;
+    use crate::request::AuthRequest;
+
+    /// The text between `start` and `end`, which are left out.
+    fn between<'a>(xml: &'a str, start: &str, end: &str) -> &'a str {
+        let from = xml.find(start).unwrap() + start.len();
+        let to = from + xml[from..].find(end).unwrap();
+        &xml[from..to]
+    }
+
+    #[test]
+    fn signature_value_verifies_against_the_user_certificate() {
+        let ca = MockCa::generate();
+        let user = UserCompletionData::new("201604064188".to_string(), "Test Testsson".to_string());
+        let order = OrderSummary {
+            id: Uuid::new_v4(),
+            ip: "127.0.0.1".parse().unwrap(),
+            order_time: OffsetDateTime::now_utc(),
+            order_type: OrderType::Sign,
+            call_initiator: None,
+            rp: None,
+            mrtd_user: None,
+            request: AuthRequest {
+                end_user_ip: "10.1.2.3".to_string(),
+                user_visible_data: Some("VGVzdA==".to_string()),
+                ..Default::default()
+            },
+            user_visible_data: None,
+            user_non_visible_data: None,
+        };
+        let completion = sign_completion(&ca, &order, &user);
+        let document = String::from_utf8(STANDARD.decode(completion.signature).unwrap()).unwrap();
+
+        let signed_info = format!(
+            "<SignedInfo{}</SignedInfo>",
+            between(&document, "<SignedInfo", "</SignedInfo>")
+        );
+        let signature_value = STANDARD
+            .decode(between(&document, "<SignatureValue>", "</SignatureValue>"))
+            .unwrap();
+        let certificate = STANDARD
+            .decode(between(
+                &document,
+                "<X509Certificate>",
+                "</X509Certificate>",
+            ))
+            .unwrap();
+        assert_eq!(
+            certificate,
+            ca.user_certificate(&user).certificate.der().as_ref()
+        );
+        let certificate = X509Certificate::from_der(&certificate).unwrap().1;
+        UnparsedPublicKey::new(
+            &ECDSA_P256_SHA256_FIXED,
+            &certificate.public_key().subject_public_key.data,
+        )
+        .verify(signed_info.as_bytes(), &signature_value)
+        .unwrap();
+
+        let signed_data = format!(
+            "<bankIdSignedData{}</bankIdSignedData>",
+            between(&document, "<bankIdSignedData", "</bankIdSignedData>")
+        );
+        assert!(signed_data.contains("<funcId>Signing</funcId>"));
+        assert_eq!(
+            between(&signed_info, "<DigestValue>", "</DigestValue>"),
+            STANDARD.encode(Sha256::digest(signed_data.as_bytes()))
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::Order;
+
+/// Where [`crate::OrderData`] keeps its orders between restarts.
+///
+/// The orders are always served from memory, a store is only handed every change to save and
+/// asked for what it has on startup.
+pub trait OrderStore: Debug + Send {
+    fn load(&self) -> HashMap<Uuid, Order>;
+    fn save(&mut self, orders: &HashMap<Uuid, Order>);
+}
+
+/// Keeps nothing, every restart starts without orders.
+#[derive(Debug, Default)]
+pub struct MemoryStore;
+
+impl OrderStore for MemoryStore {
+    fn load(&self) -> HashMap<Uuid, Order> {
+        HashMap::new()
+    }
+
+    fn save(&mut self, _orders: &HashMap<Uuid, Order>) {}
+}
+
+/// Keeps the orders as JSON in a file, rewritten on every change.
+#[derive(Debug)]
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(path: PathBuf) -> Self {
+        FileStore { path }
+    }
+}
+
+impl OrderStore for FileStore {
+    fn load(&self) -> HashMap<Uuid, Order> {
+        match std::fs::read(&self.path) {
+            Ok(json) => serde_json::from_slice(&json)
+                .unwrap_or_else(|e| panic!("Reading orders from {}: {e}", self.path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => panic!("Reading orders from {}: {e}", self.path.display()),
+        }
+    }
+
+    fn save(&mut self, orders: &HashMap<Uuid, Order>) {
+        // Written next to the file and renamed over it, so a crash never leaves half a file.
+        let temporary = self.path.with_extension("tmp");
+        let written = serde_json::to_vec(orders)
+            .map_err(std::io::Error::other)
+            .and_then(|json| std::fs::write(&temporary, json))
+            .and_then(|_| std::fs::rename(&temporary, &self.path));
+        if let Err(e) = written {
+            leptos::logging::warn!("Saving orders to {} failed: {e}", self.path.display());
+        }
+    }
+}
+
+/// Which [`OrderStore`] to use, in memory unless configured otherwise.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case", tag = "type")]
+pub enum StoreConfig {
+    #[default]
+    Memory,
+    File {
+        path: PathBuf,
+    },
+}
+
+impl StoreConfig {
+    pub fn open(&self) -> Box<dyn OrderStore> {
+        match self {
+            StoreConfig::Memory => Box::new(MemoryStore),
+            StoreConfig::File { path } => Box::new(FileStore::new(path.clone())),
+        }
+    }
+}
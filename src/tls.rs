@@ -0,0 +1,212 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::Router;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::client::danger::HandshakeSignatureValid;
+use tokio_rustls::rustls::crypto::{self, CryptoProvider};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, UnixTime};
+use tokio_rustls::rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use tokio_rustls::rustls::{
+    DigitallySignedStruct, DistinguishedName, ServerConfig, SignatureScheme,
+};
+use tokio_rustls::TlsAcceptor;
+use tower_service::Service;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::TlsConfig;
+
+/// The certificate an RP presented in the TLS handshake, added to each of its requests.
+#[derive(Clone, Debug)]
+pub struct ClientCertificate(pub CertificateDer<'static>);
+
+impl ClientCertificate {
+    /// The RP the certificate was issued to, its common name or else the whole subject.
+    pub fn rp_name(&self) -> Option<String> {
+        let (_, certificate) = X509Certificate::from_der(&self.0).ok()?;
+        let subject = certificate.subject();
+        let common_name = subject
+            .iter_common_name()
+            .next()
+            .and_then(|name| name.as_str().ok())
+            .map(str::to_string);
+        Some(common_name.unwrap_or_else(|| subject.to_string()))
+    }
+}
+
+/// The client certificates allowed to call the RP API.
+#[derive(Debug)]
+pub struct AcceptedClients {
+    certificates: Vec<CertificateDer<'static>>,
+    ca: Option<CertificateDer<'static>>,
+}
+
+impl AcceptedClients {
+    /// None when neither `client-certs` nor `client-ca` is configured, letting anyone in.
+    pub fn load(config: &TlsConfig) -> Option<Self> {
+        if config.client_certs.is_none() && config.client_ca.is_none() {
+            return None;
+        }
+        let certificates = config
+            .client_certs
+            .iter()
+            .flatten()
+            .flat_map(|path| {
+                CertificateDer::pem_file_iter(path)
+                    .unwrap_or_else(|e| panic!("Reading client cert {}: {e}", path.display()))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Parsing client certs");
+        let ca = config.client_ca.as_ref().map(|path| {
+            CertificateDer::from_pem_file(path)
+                .unwrap_or_else(|e| panic!("Reading client ca {}: {e}", path.display()))
+        });
+        Some(AcceptedClients { certificates, ca })
+    }
+
+    /// Whether the certificate is one of the configured ones, or currently valid and issued by
+    /// the configured CA.
+    pub fn accepts(&self, certificate: &ClientCertificate) -> bool {
+        if self.certificates.contains(&certificate.0) {
+            return true;
+        }
+        let Some(ca) = &self.ca else {
+            return false;
+        };
+        let (Ok((_, ca)), Ok((_, certificate))) = (
+            X509Certificate::from_der(ca),
+            X509Certificate::from_der(&certificate.0),
+        ) else {
+            return false;
+        };
+        certificate.validity().is_valid()
+            && certificate.issuer() == ca.subject()
+            && certificate.verify_signature(Some(ca.public_key())).is_ok()
+    }
+}
+
+/// Builds the TLS acceptor, with a self-signed certificate for localhost if none is configured.
+pub fn acceptor(config: &TlsConfig) -> TlsAcceptor {
+    let (chain, key) = match (&config.cert, &config.key) {
+        (Some(cert), Some(key)) => (
+            CertificateDer::pem_file_iter(cert)
+                .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                .unwrap_or_else(|e| panic!("Reading tls cert {}: {e}", cert.display())),
+            PrivateKeyDer::from_pem_file(key)
+                .unwrap_or_else(|e| panic!("Reading tls key {}: {e}", key.display())),
+        ),
+        _ => {
+            let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])
+                .expect("Generating a self-signed certificate");
+            (
+                vec![certified.cert.der().clone()],
+                PrivateKeyDer::Pkcs8(certified.key_pair.serialize_der().into()),
+            )
+        }
+    };
+
+    let provider = Arc::new(crypto::ring::default_provider());
+    let mut server_config = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .expect("The ring provider supports the default versions")
+        .with_client_cert_verifier(Arc::new(AnyClientCertificate(provider)))
+        .with_single_cert(chain, key)
+        .expect("Invalid tls cert or key");
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    TlsAcceptor::from(Arc::new(server_config))
+}
+
+/// Like `axum::serve`, but over TLS and with the client certificate on every request.
+pub async fn serve(listener: TcpListener, acceptor: TlsAcceptor, app: Router) {
+    let mut make_service = app.into_make_service_with_connect_info::<SocketAddr>();
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                leptos::logging::warn!("Accepting connection failed: {e}");
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let Ok(service) = make_service.call(addr).await;
+        tokio::spawn(async move {
+            let Ok(stream) = acceptor.accept(stream).await else {
+                return;
+            };
+            let certificate = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certificates| certificates.first())
+                .map(|certificate| ClientCertificate(certificate.clone().into_owned()));
+            let service = hyper::service::service_fn(move |mut request| {
+                if let Some(certificate) = &certificate {
+                    request.extensions_mut().insert(certificate.clone());
+                }
+                service.clone().call(request)
+            });
+            let _ = auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
+
+/// Asks for a client certificate without requiring one, so browsers can still reach the UI.
+/// Which certificates may call the RP API is decided per request by [`AcceptedClients`].
+#[derive(Debug)]
+struct AnyClientCertificate(Arc<CryptoProvider>);
+
+impl ClientCertVerifier for AnyClientCertificate {
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> Result<ClientCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}